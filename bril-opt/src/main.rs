@@ -0,0 +1,58 @@
+use bril_rs::{load_abstract_program, output_abstract_program};
+use bril_utils::pass::{default_registry, RunOptions};
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// The passes to run, e.g. `fix(lvn[prop,fold],tdce),licm`.
+    #[clap(short, long, default_value = "")]
+    passes : String,
+
+    /// Print each function to stderr after every pass.
+    #[clap(long)]
+    print_after : bool,
+
+    /// Run the verifier after every pass and exit with 1 at the first
+    /// diagnostic.
+    #[clap(long)]
+    verify_each : bool,
+
+    /// List the available passes and their options.
+    #[clap(short, long)]
+    list : bool,
+}
+
+fn main() {
+    let args = Args::parse();
+    let registry = default_registry();
+    if args.list {
+        for info in registry.passes() {
+            if info.options.is_empty() {
+                println!("{:<14}{}", info.name, info.description);
+            } else {
+                println!("{:<14}{} [{}]", info.name, info.description, info.options.join(","));
+            }
+        }
+        return;
+    }
+    let pipeline = if args.passes.is_empty() {
+        None
+    } else {
+        match registry.parse(&args.passes) {
+            Ok(p) => Some(p),
+            Err(e) => {
+                eprintln!("error: {e}");
+                std::process::exit(2);
+            },
+        }
+    };
+    let mut program = load_abstract_program();
+    if let Some(pipeline) = pipeline {
+        let options = RunOptions {print_after : args.print_after, verify_each : args.verify_each};
+        if let Err(e) = pipeline.run(&mut program, options) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+    output_abstract_program(&program);
+}
@@ -0,0 +1,185 @@
+use bril_rs::{AbstractFunction, AbstractCode, AbstractInstruction};
+
+use crate::cfg::{Cfg, form_cfg, reassemble};
+use crate::chains::{def_use_chains, DefUseChains, Use};
+use crate::control_dep::{control_dependence, ControlDeps};
+use crate::df::ProgramPoint;
+use crate::dominators::VIRTUAL_EXIT;
+use crate::form_blocks::{form_blocks, get_args};
+use std::collections::HashSet;
+
+/// Instructions that are live whether or not their results are used: they
+/// have effects outside the function or leave it.
+fn is_critical(instr : &AbstractInstruction) -> bool {
+    match instr {
+        AbstractInstruction::Constant {..} => false,
+        AbstractInstruction::Value {op, ..} => op == "call",
+        AbstractInstruction::Effect {op, ..} => !matches!(op.as_str(), "jmp" | "br" | "nop"),
+    }
+}
+
+fn get_instr(cfg : &Cfg, point : ProgramPoint) -> Option<&AbstractInstruction> {
+    match cfg.block_map.get(&point.block).unwrap().instrs.get(point.index) {
+        Some(AbstractCode::Instruction(instr)) => Some(instr),
+        _ => None,
+    }
+}
+
+fn terminator(cfg : &Cfg, block : i32) -> ProgramPoint {
+    ProgramPoint::new(block, cfg.block_map.get(&block).unwrap().len() - 1)
+}
+
+struct Marker<'a> {
+    cfg : &'a Cfg,
+    chains : DefUseChains,
+    cd : ControlDeps,
+    live : HashSet<ProgramPoint>,
+    live_blocks : HashSet<i32>,
+    worklist : Vec<ProgramPoint>,
+}
+
+impl<'a> Marker<'a> {
+    fn mark(&mut self, point : ProgramPoint) {
+        if self.live.insert(point) {
+            self.worklist.push(point);
+        }
+    }
+
+    /// Marks the instructions that feed `point`, the branches deciding
+    /// whether its block runs and, for a phi, the edges it selects between.
+    fn propagate(&mut self) {
+        while let Some(point) = self.worklist.pop() {
+            let instr = get_instr(self.cfg, point).unwrap();
+            for a in get_args(instr) {
+                let defs : Vec<ProgramPoint> = self.chains.defs(&Use::new(a.to_string(), point))
+                    .iter().filter_map(|d| d.point).collect();
+                for d in defs {
+                    self.mark(d);
+                }
+            }
+            if let AbstractInstruction::Value {op, labels, ..} = instr {
+                if op == "phi" {
+                    for l in labels {
+                        if let Some(pred) = self.cfg.name_map.get_by_right(l) {
+                            self.mark(terminator(self.cfg, *pred));
+                        }
+                    }
+                }
+            }
+            if self.live_blocks.insert(point.block) {
+                for b in self.cd.deps(&point.block).to_vec() {
+                    self.mark(terminator(self.cfg, b));
+                }
+            }
+        }
+    }
+
+    /// The closest block strictly post-dominating `block` that holds a live
+    /// instruction, if any before the virtual exit.
+    fn live_post_dom(&self, block : i32) -> Option<i32> {
+        let mut runner = self.cd.post_dom_tree.idom(&block);
+        while let Some(r) = runner {
+            if r == VIRTUAL_EXIT {
+                return None;
+            }
+            if self.live_blocks.contains(&r) {
+                return Some(r);
+            }
+            runner = self.cd.post_dom_tree.idom(&r);
+        }
+        None
+    }
+
+    /// Branches with no live post-dominator sit on paths that never reach a
+    /// return, so there is nowhere to jump instead; keep them.
+    fn keep_exitless_branches(&mut self) -> bool {
+        let mut blocks : Vec<i32> = self.cfg.block_map.keys().copied().collect();
+        blocks.sort();
+        let mut marked = false;
+        for b in blocks {
+            let term = terminator(self.cfg, b);
+            let is_br = matches!(get_instr(self.cfg, term),
+                Some(AbstractInstruction::Effect {op, ..}) if op == "br");
+            if is_br && !self.live.contains(&term) && self.live_post_dom(b).is_none() {
+                self.mark(term);
+                marked = true;
+            }
+        }
+        marked
+    }
+}
+
+fn jmp(label : &str) -> AbstractCode {
+    AbstractCode::Instruction(AbstractInstruction::Effect {
+        op : "jmp".to_string(),
+        args : vec![],
+        funcs : vec![],
+        labels : vec![label.to_string()],
+    })
+}
+
+/// Aggressive dead code elimination. Starts from the instructions with side
+/// effects and marks everything they depend on through use-def chains and
+/// control dependence; all other instructions are deleted and branches that
+/// no live code depends on become jumps to their nearest live
+/// post-dominator. Like any ADCE, this assumes that loops with an exit
+/// terminate. Returns whether the function changed.
+pub fn adce(func : &mut AbstractFunction) -> bool {
+    let mut cfg = form_cfg(form_blocks(func));
+    let args : Vec<String> = func.args.iter().map(|a| a.name.clone()).collect();
+
+    let mut marker = Marker {
+        cfg : &cfg,
+        chains : def_use_chains(&cfg, &args),
+        cd : control_dependence(&cfg),
+        live : HashSet::new(),
+        live_blocks : HashSet::new(),
+        worklist : vec![],
+    };
+    for (num, block) in &cfg.block_map {
+        for (i, code) in block.instrs.iter().enumerate() {
+            if let AbstractCode::Instruction(instr) = code {
+                if is_critical(instr) {
+                    marker.mark(ProgramPoint::new(*num, i));
+                }
+            }
+        }
+    }
+    marker.propagate();
+    while marker.keep_exitless_branches() {
+        marker.propagate();
+    }
+
+    let mut new_succ = vec![];
+    let mut changed = false;
+    let mut blocks : Vec<i32> = cfg.block_map.keys().copied().collect();
+    blocks.sort();
+    for b in blocks {
+        let term = terminator(&cfg, b);
+        let target = match get_instr(&cfg, term) {
+            Some(AbstractInstruction::Effect {op, ..}) if op == "br" && !marker.live.contains(&term) =>
+                marker.live_post_dom(b),
+            _ => None,
+        };
+        let live : Vec<usize> = (0..term.index).filter(|i| {
+            marker.live.contains(&ProgramPoint::new(b, *i))
+        }).collect();
+        changed |= live.len() != term.index || target.is_some();
+        new_succ.push((b, live, target));
+    }
+
+    for (b, live, target) in new_succ {
+        let block = cfg.block_map.get_mut(&b).unwrap();
+        let term = block.instrs.pop().unwrap();
+        let mut instrs : Vec<AbstractCode> = live.into_iter().map(|i| block.instrs[i].clone()).collect();
+        instrs.push(term);
+        block.instrs = instrs;
+        if let Some(t) = target {
+            cfg.replace_terminator(b, jmp(cfg.name(t)));
+        }
+    }
+
+    changed |= cfg.remove_unreachable();
+    func.instrs = reassemble(cfg);
+    changed
+}
@@ -0,0 +1,104 @@
+/// A set of small integers, such as the indices of `VarId`s or of numbered
+/// definitions, stored as one bit each.
+#[derive(Debug, Clone, Default)]
+pub struct BitSet {
+    words : Vec<u64>,
+}
+
+impl BitSet {
+    /// An empty set that can hold `0..size` without growing.
+    pub fn new(size : usize) -> Self {
+        BitSet {words : vec![0; size.div_ceil(64)]}
+    }
+
+    pub fn insert(&mut self, i : usize) -> bool {
+        let (w, b) = (i / 64, i % 64);
+        if w >= self.words.len() {
+            self.words.resize(w + 1, 0);
+        }
+        let fresh = self.words[w] & (1 << b) == 0;
+        self.words[w] |= 1 << b;
+        fresh
+    }
+
+    pub fn remove(&mut self, i : usize) -> bool {
+        let (w, b) = (i / 64, i % 64);
+        match self.words.get_mut(w) {
+            Some(word) if *word & (1 << b) != 0 => {
+                *word &= !(1 << b);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    pub fn contains(&self, i : usize) -> bool {
+        self.words.get(i / 64).is_some_and(|w| w & (1 << (i % 64)) != 0)
+    }
+
+    /// Adds every element of `other`. Returns whether the set grew.
+    pub fn union_with(&mut self, other : &BitSet) -> bool {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        let mut changed = false;
+        for (w, o) in self.words.iter_mut().zip(&other.words) {
+            let new = *w | o;
+            changed |= new != *w;
+            *w = new;
+        }
+        changed
+    }
+
+    pub fn intersect_with(&mut self, other : &BitSet) {
+        for (i, w) in self.words.iter_mut().enumerate() {
+            *w &= other.words.get(i).copied().unwrap_or(0);
+        }
+    }
+
+    pub fn difference_with(&mut self, other : &BitSet) {
+        for (w, o) in self.words.iter_mut().zip(&other.words) {
+            *w &= !o;
+        }
+    }
+
+    pub fn is_subset(&self, other : &BitSet) -> bool {
+        self.words.iter().enumerate().all(|(i, w)| w & !other.words.get(i).copied().unwrap_or(0) == 0)
+    }
+
+    pub fn clear(&mut self) {
+        self.words.iter_mut().for_each(|w| *w = 0);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// The elements in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item=usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, w)| {
+            let mut w = *w;
+            std::iter::from_fn(move || {
+                if w == 0 {
+                    return None;
+                }
+                let b = w.trailing_zeros() as usize;
+                w &= w - 1;
+                Some(i * 64 + b)
+            })
+        })
+    }
+}
+
+impl PartialEq for BitSet {
+    fn eq(&self, other : &BitSet) -> bool {
+        let n = self.words.len().max(other.words.len());
+        (0..n).all(|i| self.words.get(i).unwrap_or(&0) == other.words.get(i).unwrap_or(&0))
+    }
+}
+
+impl Eq for BitSet {}
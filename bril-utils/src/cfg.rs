@@ -1,7 +1,7 @@
-use crate::form_blocks::{Block, flatten_blocks};
+use crate::form_blocks::{Block, is_terminator};
 use bril_rs::{AbstractCode, AbstractInstruction};
 use indexmap::IndexMap;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::iter::Iterator;
 use bimap::BiMap;
 
@@ -11,27 +11,370 @@ pub struct Cfg {
     pub pred : HashMap<i32, Vec<i32>>,
     pub block_map : IndexMap<i32, Block>,
     pub name_map : BiMap<i32, String>,
+    /// Number for the next block, so that numbers are never reused.
+    next_num : i32,
 }
 
 impl Cfg {
+    /// Adds `block` at the end of the function without any edges. Use
+    /// `insert_block` to add a block together with the edges of its
+    /// terminator.
     pub fn add_block(&mut self, name : String, block : Block) -> i32 {
-        let num = self.name_map.len() as i32;
+        let num = self.next_num;
+        self.next_num += 1;
         self.name_map.insert(num, name);
         self.block_map.insert(num, block);
+        self.succ.entry(num).or_default();
+        self.pred.entry(num).or_default();
         num
     }
     
     pub fn add_pred(&mut self, num : i32, pred : i32) {
         self.pred.get_mut(&num).unwrap().push(pred);
     }
+
+    pub fn entry(&self) -> Option<i32> {
+        self.block_map.first().map(|(num, _)| *num)
+    }
+
+    pub fn name(&self, num : i32) -> &String {
+        self.name_map.get_by_left(&num).unwrap()
+    }
+
+    pub fn num(&self, name : &String) -> i32 {
+        *self.name_map.get_by_right(name).unwrap()
+    }
+
+    /// A block name based on `seed` that is not used yet.
+    pub fn fresh_name(&self, seed : &str) -> String {
+        (1..).map(|i| format!("{seed}{i}")).find(|name| !self.name_map.contains_right(name)).unwrap()
+    }
+
+    fn add_edge(&mut self, from : i32, to : i32) {
+        self.succ.get_mut(&from).unwrap().push(to);
+        self.pred.get_mut(&to).unwrap().push(from);
+    }
+
+    /// Moves block `num` just before block `before` in the layout, or to the
+    /// end when `before` is `None`.
+    pub fn move_block(&mut self, num : i32, before : Option<i32>) {
+        let from = self.block_map.get_index_of(&num).unwrap();
+        let to = match before {
+            Some(before) => {
+                let i = self.block_map.get_index_of(&before).unwrap();
+                if from < i { i - 1 } else { i }
+            },
+            None => self.block_map.len() - 1,
+        };
+        self.block_map.move_index(from, to);
+    }
+
+    /// Adds `block` before block `before` (or at the end) and connects it to
+    /// the targets of its terminator, which must already exist.
+    pub fn insert_block(&mut self, name : String, block : Block, before : Option<i32>) -> i32 {
+        let targets : Vec<i32> = match block.instrs.last() {
+            Some(code) => term_sucessors(code).map(|labels|
+                labels.iter().map(|l| self.num(l)).collect()).unwrap_or_default(),
+            None => vec![],
+        };
+        let num = self.add_block(name, block);
+        self.move_block(num, before);
+        for t in targets {
+            self.add_edge(num, t);
+        }
+        num
+    }
+
+    /// Removes every edge `from -> to` and the phi operands in `to` that flow
+    /// along it. The terminator of `from` is left as it is.
+    pub fn remove_edge(&mut self, from : i32, to : i32) {
+        self.succ.get_mut(&from).unwrap().retain(|s| *s != to);
+        self.pred.get_mut(&to).unwrap().retain(|p| *p != from);
+        self.remove_phi_operands(to, from);
+    }
+
+    /// Replaces the terminator of block `num` with `term` and updates its
+    /// outgoing edges to match. Phi operands from `num` are dropped in the
+    /// blocks it no longer jumps to.
+    pub fn replace_terminator(&mut self, num : i32, term : AbstractCode) {
+        let targets : Vec<i32> = term_sucessors(&term).map(|labels|
+            labels.iter().map(|l| self.num(l)).collect()).unwrap_or_default();
+        let block = self.block_map.get_mut(&num).unwrap();
+        match block.instrs.last() {
+            Some(AbstractCode::Instruction(instr)) if is_terminator(instr) =>
+                *block.instrs.last_mut().unwrap() = term,
+            _ => block.instrs.push(term),
+        }
+        for s in std::mem::take(self.succ.get_mut(&num).unwrap()) {
+            self.pred.get_mut(&s).unwrap().retain(|p| *p != num);
+            if !targets.contains(&s) {
+                self.remove_phi_operands(s, num);
+            }
+        }
+        for t in targets {
+            self.add_edge(num, t);
+        }
+    }
+
+    /// Removes block `num` with all of its edges, and the phi operands that
+    /// came from it. Terminators jumping to the block are not rewritten, so it
+    /// should be unreachable or have its incoming edges redirected first.
+    pub fn remove_block(&mut self, num : i32) {
+        for s in self.succ.remove(&num).unwrap() {
+            if s != num && self.block_map.contains_key(&s) {
+                self.pred.get_mut(&s).unwrap().retain(|p| *p != num);
+                self.remove_phi_operands(s, num);
+            }
+        }
+        for p in self.pred.remove(&num).unwrap() {
+            if let Some(succ) = self.succ.get_mut(&p) {
+                succ.retain(|s| *s != num);
+            }
+        }
+        self.block_map.shift_remove(&num);
+        self.name_map.remove_by_left(&num);
+    }
+
+    /// The blocks that can be reached from the entry.
+    pub fn reachable(&self) -> HashSet<i32> {
+        let mut reachable = HashSet::new();
+        let mut stack : Vec<i32> = self.entry().into_iter().collect();
+        while let Some(b) = stack.pop() {
+            if reachable.insert(b) {
+                stack.extend(self.succ.get(&b).unwrap());
+            }
+        }
+        reachable
+    }
+
+    /// Removes the blocks that cannot be reached from the entry. Returns
+    /// whether any block was removed.
+    pub fn remove_unreachable(&mut self) -> bool {
+        let reachable = self.reachable();
+        let unreachable : Vec<i32> = self.block_map.keys().copied()
+            .filter(|num| !reachable.contains(num)).collect();
+        for num in &unreachable {
+            self.remove_block(*num);
+        }
+        !unreachable.is_empty()
+    }
+
+    fn phis_mut(&mut self, num : i32) -> impl Iterator<Item=(&mut Vec<String>, &mut Vec<String>)> {
+        self.block_map.get_mut(&num).unwrap().instrs.iter_mut().filter_map(|code| match code {
+            AbstractCode::Instruction(AbstractInstruction::Value {op, args, labels, ..})
+                if op == "phi" => Some((args, labels)),
+            _ => None,
+        })
+    }
+
+    /// Drops the operands of the phis in `block` that come from `pred`.
+    pub fn remove_phi_operands(&mut self, block : i32, pred : i32) {
+        let label = self.name(pred).to_string();
+        for (args, labels) in self.phis_mut(block) {
+            let (new_args, new_labels) = args.iter().zip(labels.iter())
+                .filter(|(_, l)| **l != label)
+                .map(|(a, l)| (a.to_string(), l.to_string()))
+                .unzip();
+            *args = new_args;
+            *labels = new_labels;
+        }
+    }
+
+    fn relabel_phis(&mut self, block : i32, old : i32, new : i32) {
+        let old = self.name(old).to_string();
+        let new = self.name(new).to_string();
+        for (_, labels) in self.phis_mut(block) {
+            for l in labels.iter_mut() {
+                if *l == old {
+                    *l = new.to_string();
+                }
+            }
+        }
+    }
+
+    /// Makes every edge `from -> old_to` go to `new_to` instead, rewriting
+    /// the labels of the terminator of `from`. Phi operands in `old_to` that
+    /// came from `from` now come from `new_to` when it is a predecessor of
+    /// `old_to`, as when an edge is split, and are dropped otherwise.
+    pub fn redirect_edge(&mut self, from : i32, old_to : i32, new_to : i32) {
+        let old_label = self.name(old_to).to_string();
+        let new_label = self.name(new_to).to_string();
+        if let Some(labels) = self.block_map.get_mut(&from).unwrap().instrs.last_mut()
+            .and_then(term_labels_mut) {
+            for l in labels.iter_mut() {
+                if *l == old_label {
+                    *l = new_label.to_string();
+                }
+            }
+        }
+        let mut count = 0;
+        for s in self.succ.get_mut(&from).unwrap().iter_mut() {
+            if *s == old_to {
+                *s = new_to;
+                count += 1;
+            }
+        }
+        self.pred.get_mut(&old_to).unwrap().retain(|p| *p != from);
+        for _ in 0..count {
+            self.pred.get_mut(&new_to).unwrap().push(from);
+        }
+        if self.pred.get(&old_to).unwrap().contains(&new_to) {
+            self.relabel_phis(old_to, from, new_to);
+        } else {
+            self.remove_phi_operands(old_to, from);
+        }
+    }
+
+    /// Inserts an empty block on the edge `from -> to`, placed just before
+    /// `to`, and returns it.
+    pub fn split_edge(&mut self, from : i32, to : i32) -> i32 {
+        let name = self.fresh_name(&format!("{}.{}.", self.name(from), self.name(to)));
+        let block = Block {instrs : vec![jmp(self.name(to))]};
+        let num = self.insert_block(name, block, Some(to));
+        self.redirect_edge(from, to, num);
+        num
+    }
+
+    /// Whether `from -> to` leaves a block with several successors for a
+    /// block with several predecessors.
+    pub fn is_critical_edge(&self, from : i32, to : i32) -> bool {
+        let distinct = |edges : &Vec<i32>| edges.iter().collect::<HashSet<_>>().len();
+        distinct(self.succ.get(&from).unwrap()) > 1 && distinct(self.pred.get(&to).unwrap()) > 1
+    }
+
+    /// Splits every critical edge. Returns whether any edge was split.
+    pub fn split_critical_edges(&mut self) -> bool {
+        let mut edges = vec![];
+        for (from, succ) in &self.succ {
+            for to in succ {
+                if self.is_critical_edge(*from, *to) && !edges.contains(&(*from, *to)) {
+                    edges.push((*from, *to));
+                }
+            }
+        }
+        edges.sort();
+        for (from, to) in &edges {
+            self.split_edge(*from, *to);
+        }
+        !edges.is_empty()
+    }
+
+    /// Splits block `num` before the instruction at `index`. The instructions
+    /// from `index` on move to a new block placed right after it, which the
+    /// original block jumps to. Returns the new block.
+    pub fn split_block(&mut self, num : i32, index : usize) -> i32 {
+        let name = self.fresh_name(&format!("{}.split", self.name(num)));
+        let block = self.block_map.get_mut(&num).unwrap();
+        let tail = block.instrs.split_off(index);
+        block.instrs.push(jmp(&name));
+
+        let next = self.block_map.get_index_of(&num).unwrap() + 1;
+        let before = self.block_map.get_index(next).map(|(n, _)| *n);
+        let new = self.add_block(name, Block {instrs : tail});
+        self.move_block(new, before);
+
+        let succ = std::mem::take(self.succ.get_mut(&num).unwrap());
+        for s in &succ {
+            for p in self.pred.get_mut(s).unwrap().iter_mut() {
+                if *p == num {
+                    *p = new;
+                }
+            }
+        }
+        let mut distinct = succ.clone();
+        distinct.sort();
+        distinct.dedup();
+        for s in distinct {
+            self.relabel_phis(s, num, new);
+        }
+        self.succ.insert(new, succ);
+        self.add_edge(num, new);
+        new
+    }
+
+    /// Merges `b` into `a` when `a` only flows into `b` and `b` is only
+    /// entered from `a`. Phis in `b` become copies of their operand from
+    /// `a`; a phi with no such operand, or an `__undefined` one, never
+    /// defines its destination and is dropped. Returns whether the blocks
+    /// were merged.
+    pub fn merge_blocks(&mut self, a : i32, b : i32) -> bool {
+        let straight = a != b && self.succ.get(&a).unwrap().iter().all(|s| *s == b)
+            && !self.succ.get(&a).unwrap().is_empty()
+            && self.pred.get(&b).unwrap().iter().all(|p| *p == a)
+            && self.entry() != Some(b);
+        if !straight {
+            return false;
+        }
+
+        let a_label = self.name(a).to_string();
+        let mut tail = vec![];
+        for mut code in self.block_map.get(&b).unwrap().instrs.clone() {
+            if let AbstractCode::Instruction(AbstractInstruction::Value {op, args, labels, ..}) = &mut code {
+                if op == "phi" {
+                    let arg = args.iter().zip(labels.iter())
+                        .find(|(_, l)| **l == a_label).map(|(arg, _)| arg.to_string());
+                    match arg {
+                        Some(arg) if arg != "__undefined" => {
+                            *op = "id".to_string();
+                            *args = vec![arg];
+                            labels.clear();
+                        },
+                        _ => continue,
+                    }
+                }
+            }
+            tail.push(code);
+        }
+        let block = self.block_map.get_mut(&a).unwrap();
+        block.instrs.pop();
+        block.instrs.extend(tail);
+
+        let succ = self.succ.get(&b).unwrap().clone();
+        let mut distinct = succ.clone();
+        distinct.sort();
+        distinct.dedup();
+        for s in distinct {
+            if s != b {
+                self.relabel_phis(s, b, a);
+            }
+            for p in self.pred.get_mut(&s).unwrap().iter_mut() {
+                if *p == b {
+                    *p = a;
+                }
+            }
+        }
+        let succ = succ.into_iter().map(|s| if s == b { a } else { s }).collect();
+        self.succ.insert(a, succ);
+        self.succ.get_mut(&b).unwrap().clear();
+        self.pred.get_mut(&b).unwrap().clear();
+        self.remove_block(b);
+        true
+    }
+}
+
+fn jmp(label : &str) -> AbstractCode {
+    AbstractCode::Instruction(AbstractInstruction::Effect {
+        op : "jmp".to_string(),
+        args : vec![],
+        funcs : vec![],
+        labels : vec![label.to_string()],
+    })
+}
+
+fn term_labels_mut(code : &mut AbstractCode) -> Option<&mut Vec<String>> {
+    match code {
+        AbstractCode::Instruction(AbstractInstruction::Effect {op, labels, ..})
+            if op == "jmp" || op == "br" => Some(labels),
+        _ => None,
+    }
 }
 
-fn fresh<'a>(seed : String, mut names : impl Iterator<Item=&'a String>) -> String {
+pub fn fresh<'a>(seed : String, names : impl Iterator<Item=&'a String>) -> String {
+    let names : HashSet<&String> = names.collect();
     let mut i : i32 = 1;
     loop {
         let name = seed.to_string() + &i.to_string();
-        let contains = names.find(|a| a.to_string() == name);
-        if let None = contains {
+        if !names.contains(&name) {
             break name;
         }
         i += 1;   
@@ -76,11 +419,13 @@ fn add_terminators(mut block_map : IndexMap<String, Block>) -> IndexMap<String,
             }
         } else if let AbstractCode::Instruction(last_instr) = block.instrs.last().unwrap() {
             if i == last {
-                instr = Some(AbstractInstruction::Effect {
-                    op : "ret".to_string(), 
-                    args : vec![], 
-                    funcs : vec![],
-                    labels : vec![]});
+                if !is_terminator(last_instr) {
+                    instr = Some(AbstractInstruction::Effect {
+                        op : "ret".to_string(), 
+                        args : vec![], 
+                        funcs : vec![],
+                        labels : vec![]});
+                }
             } else if let AbstractInstruction::Effect {op, ..}
                 | AbstractInstruction::Value {op, ..} = last_instr {
                 let (dest, _) = block_map.get_index(i + 1).unwrap();
@@ -116,16 +461,11 @@ fn add_terminators(mut block_map : IndexMap<String, Block>) -> IndexMap<String,
 
 fn term_sucessors(instr : &AbstractCode) -> Option<&Vec<String>>{
     match instr {
-        AbstractCode::Instruction(instr) => {
-            match instr {
-                AbstractInstruction::Effect {op, labels, ..}=> {
-                    match op.as_str() {
-                        "jmp" | "br" => Some(labels),
-                        "ret" => None,
-                        _ => panic!("Not a terminator"),
-                    }
-                }
-                _ => panic!("Not a terminator")
+        AbstractCode::Instruction(AbstractInstruction::Effect {op, labels, ..}) => {
+            match op.as_str() {
+                "jmp" | "br" => Some(labels),
+                "ret" => None,
+                _ => panic!("Not a terminator"),
             }
         }
         _ => panic!("Not a terminator")
@@ -145,7 +485,7 @@ fn add_edges(block_map : IndexMap<String, Block>) -> Cfg {
         if let Some(successors) = term_sucessors(block.instrs.last().unwrap()) {
             for succ in successors {
                 let s = cfg.name_map.get_by_right(succ).unwrap();
-                cfg.succ.get_mut(&name).unwrap().push(*s);
+                cfg.succ.get_mut(name).unwrap().push(*s);
                 cfg.pred.get_mut(s).unwrap().push(*name);
             }
         }
@@ -156,19 +496,8 @@ fn add_edges(block_map : IndexMap<String, Block>) -> Cfg {
 fn add_entry(mut cfg : Cfg) -> Cfg {
     let (&first_num, _) = cfg.block_map.get_index(0).unwrap();
     let first_label = cfg.name_map.get_by_left(&first_num).unwrap().to_string();
-    let blocks : Vec<Block> = cfg.block_map.clone().into_values().collect();
-
-    let mut has_in_edge = false;
-
-    for instr in flatten_blocks(blocks) {
-        if let AbstractCode::Instruction(instr) = instr {
-            match instr {
-                AbstractInstruction::Effect {labels, ..}
-                    if labels.contains(&first_label) => has_in_edge = true,
-                _ => (),
-            }
-        }
-    }
+    let has_in_edge = cfg.block_map.values().flat_map(|b| &b.instrs).any(|code| matches!(code,
+        AbstractCode::Instruction(AbstractInstruction::Effect {labels, ..}) if labels.contains(&first_label)));
     if has_in_edge {
         let new_label = fresh("entry".to_string(), cfg.block_map.keys()
             .map(|i| cfg.name_map.get_by_left(i).unwrap()));
@@ -0,0 +1,91 @@
+use bril_rs::{AbstractCode, AbstractInstruction};
+
+use crate::cfg::Cfg;
+use crate::df::{Def, ProgramPoint, ReachingDefs};
+use crate::form_blocks::get_args;
+use std::collections::HashMap;
+
+/// A read of `var` by the instruction at `point`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Use {
+    pub var : String,
+    pub point : ProgramPoint,
+}
+
+impl Use {
+    pub fn new(var : String, point : ProgramPoint) -> Self {
+        Use {var, point}
+    }
+}
+
+/// Def-use and use-def chains built from reaching definitions. A phi operand
+/// is a use at the phi itself, reached by the definitions live at the end of
+/// the predecessor named by its label.
+#[derive(Debug, Default, Clone)]
+pub struct DefUseChains {
+    pub def_use : HashMap<Def, Vec<Use>>,
+    pub use_def : HashMap<Use, Vec<Def>>,
+}
+
+impl DefUseChains {
+    pub fn uses(&self, def : &Def) -> &[Use] {
+        self.def_use.get(def).map(|u| u.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn defs(&self, u : &Use) -> &[Def] {
+        self.use_def.get(u).map(|d| d.as_slice()).unwrap_or(&[])
+    }
+
+    fn add(&mut self, u : Use, defs : Vec<Def>) {
+        // A phi can read the same variable along several edges.
+        let mut defs : Vec<Def> = defs.into_iter()
+            .chain(self.use_def.remove(&u).unwrap_or_default()).collect();
+        defs.sort();
+        defs.dedup();
+        for d in &defs {
+            let uses = self.def_use.get_mut(d).unwrap();
+            if !uses.contains(&u) {
+                uses.push(u.clone());
+            }
+        }
+        self.use_def.insert(u, defs);
+    }
+}
+
+pub fn def_use_chains(cfg : &Cfg, args : &[String]) -> DefUseChains {
+    let defs = ReachingDefs::new(cfg, args);
+    let mut chains = DefUseChains::default();
+    for d in defs.defs() {
+        chains.def_use.insert(d.clone(), vec![]);
+    }
+
+    for (num, block) in &cfg.block_map {
+        let mut reaching = defs.block_in_set(*num).clone();
+        for (i, code) in block.instrs.iter().enumerate() {
+            if let AbstractCode::Instruction(instr) = code {
+                let point = ProgramPoint::new(*num, i);
+                match instr {
+                    AbstractInstruction::Value {op, args, labels, ..} if op == "phi" => {
+                        for (a, label) in args.iter().zip(labels) {
+                            let pred_defs = match cfg.name_map.get_by_right(label) {
+                                Some(pred) => defs.block_out_set(*pred),
+                                None => continue,
+                            };
+                            chains.add(Use::new(a.to_string(), point), defs.var_defs_in(a, pred_defs));
+                        }
+                    },
+                    _ => {
+                        for a in get_args(instr) {
+                            chains.add(Use::new(a.to_string(), point), defs.var_defs_in(a, &reaching));
+                        }
+                    },
+                }
+                defs.step(point, instr, &mut reaching);
+            }
+        }
+    }
+    for uses in chains.def_use.values_mut() {
+        uses.sort();
+    }
+    chains
+}
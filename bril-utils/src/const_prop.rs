@@ -0,0 +1,302 @@
+use bril_rs::{AbstractFunction, AbstractCode, AbstractInstruction, ConstOps, Literal};
+
+use crate::cfg::{Cfg, form_cfg, reassemble};
+use crate::df::{Dataflow, Direction, Lattice, ProgramPoint, df_analysis};
+use crate::form_blocks::{form_blocks, get_dest};
+use std::collections::HashMap;
+
+/// The three-level constant lattice: `Undefined` < `Const(c)` < `Overdefined`.
+#[derive(Debug, Clone)]
+pub enum ConstValue {
+    Undefined,
+    Const(Literal),
+    Overdefined,
+}
+
+fn same_literal(a : &Literal, b : &Literal) -> bool {
+    match (a, b) {
+        (Literal::Int(x), Literal::Int(y)) => x == y,
+        (Literal::Bool(x), Literal::Bool(y)) => x == y,
+        // Compare bit patterns so that a NaN is equal to itself and the
+        // analysis still reaches a fixed point.
+        (Literal::Float(x), Literal::Float(y)) => x.to_bits() == y.to_bits(),
+        _ => false,
+    }
+}
+
+impl PartialEq for ConstValue {
+    fn eq(&self, other : &Self) -> bool {
+        match (self, other) {
+            (ConstValue::Undefined, ConstValue::Undefined) => true,
+            (ConstValue::Overdefined, ConstValue::Overdefined) => true,
+            (ConstValue::Const(a), ConstValue::Const(b)) => same_literal(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Lattice for ConstValue {
+    fn join(&self, other : &Self) -> Self {
+        match (self, other) {
+            (ConstValue::Undefined, x) | (x, ConstValue::Undefined) => x.clone(),
+            (ConstValue::Const(a), ConstValue::Const(b)) if same_literal(a, b) => self.clone(),
+            _ => ConstValue::Overdefined,
+        }
+    }
+
+    fn meet(&self, other : &Self) -> Self {
+        match (self, other) {
+            (ConstValue::Overdefined, x) | (x, ConstValue::Overdefined) => x.clone(),
+            (ConstValue::Const(a), ConstValue::Const(b)) if same_literal(a, b) => self.clone(),
+            _ => ConstValue::Undefined,
+        }
+    }
+}
+
+fn binop_int(args : &[Literal], func : &dyn Fn(i64, i64) -> Option<Literal>) -> Option<Literal> {
+    match args {
+        [Literal::Int(x), Literal::Int(y)] => func(*x, *y),
+        _ => None,
+    }
+}
+
+fn binop_float(args : &[Literal], func : &dyn Fn(f64, f64) -> Literal) -> Option<Literal> {
+    match args {
+        [Literal::Float(x), Literal::Float(y)] => match func(*x, *y) {
+            // Bril's JSON form cannot represent infinities or NaN.
+            Literal::Float(z) if !z.is_finite() => None,
+            l => Some(l),
+        },
+        _ => None,
+    }
+}
+
+fn binop_bool(args : &[Literal], func : &dyn Fn(bool, bool) -> bool) -> Option<Literal> {
+    match args {
+        [Literal::Bool(x), Literal::Bool(y)] => Some(Literal::Bool(func(*x, *y))),
+        _ => None,
+    }
+}
+
+/// Evaluates a pure value operation on constant arguments. Returns `None` for
+/// operations that cannot be folded, including division by zero.
+pub fn eval_op(op : &str, args : &[Literal]) -> Option<Literal> {
+    match op {
+        "id" => args.first().cloned(),
+        "add"  => binop_int(args, &|a, b| Some(Literal::Int(a.wrapping_add(b)))),
+        "mul"  => binop_int(args, &|a, b| Some(Literal::Int(a.wrapping_mul(b)))),
+        "sub"  => binop_int(args, &|a, b| Some(Literal::Int(a.wrapping_sub(b)))),
+        "div"  => binop_int(args, &|a, b| a.checked_div(b).map(Literal::Int)),
+        "eq"   => binop_int(args, &|a, b| Some(Literal::Bool(a == b))),
+        "lt"   => binop_int(args, &|a, b| Some(Literal::Bool(a < b))),
+        "gt"   => binop_int(args, &|a, b| Some(Literal::Bool(a > b))),
+        "le"   => binop_int(args, &|a, b| Some(Literal::Bool(a <= b))),
+        "ge"   => binop_int(args, &|a, b| Some(Literal::Bool(a >= b))),
+        "fadd" => binop_float(args, &|a, b| Literal::Float(a + b)),
+        "fmul" => binop_float(args, &|a, b| Literal::Float(a * b)),
+        "fsub" => binop_float(args, &|a, b| Literal::Float(a - b)),
+        "fdiv" => binop_float(args, &|a, b| Literal::Float(a / b)),
+        "feq"  => binop_float(args, &|a, b| Literal::Bool(a == b)),
+        "flt"  => binop_float(args, &|a, b| Literal::Bool(a < b)),
+        "fgt"  => binop_float(args, &|a, b| Literal::Bool(a > b)),
+        "fle"  => binop_float(args, &|a, b| Literal::Bool(a <= b)),
+        "fge"  => binop_float(args, &|a, b| Literal::Bool(a >= b)),
+        "and"  => binop_bool(args, &|a, b| a && b),
+        "or"   => binop_bool(args, &|a, b| a || b),
+        "not"  => match args {
+            [Literal::Bool(x)] => Some(Literal::Bool(!x)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Computes the lattice value of a pure value operation from the values of
+/// its arguments. `and` and `or` are decided by a single constant argument.
+pub fn eval_value(op : &str, args : &[ConstValue]) -> ConstValue {
+    match op {
+        "and" | "or" => {
+            let short = op == "or";
+            if args.iter().any(|a| matches!(a, ConstValue::Const(Literal::Bool(b)) if *b == short)) {
+                return ConstValue::Const(Literal::Bool(short));
+            }
+        },
+        "phi" => {
+            return args.iter().fold(ConstValue::Undefined, |acc, a| acc.join(a));
+        },
+        _ => (),
+    }
+    if args.contains(&ConstValue::Overdefined) {
+        return ConstValue::Overdefined;
+    }
+    let mut lits = Vec::new();
+    for a in args {
+        match a {
+            ConstValue::Const(l) => lits.push(l.clone()),
+            _ => return ConstValue::Undefined,
+        }
+    }
+    match eval_op(op, &lits) {
+        Some(l) => ConstValue::Const(l),
+        None => ConstValue::Overdefined,
+    }
+}
+
+pub fn is_foldable(op : &str) -> bool {
+    matches!(op, "id" | "add" | "mul" | "sub" | "div" | "eq" | "lt" | "gt" | "le" | "ge"
+        | "fadd" | "fmul" | "fsub" | "fdiv" | "feq" | "flt" | "fgt" | "fle" | "fge"
+        | "and" | "or" | "not" | "phi")
+}
+
+/// Values of variables at each point, with absent variables `Undefined`.
+pub type ConstEnv = HashMap<String, ConstValue>;
+
+fn lookup(env : &ConstEnv, var : &String) -> ConstValue {
+    env.get(var).cloned().unwrap_or(ConstValue::Undefined)
+}
+
+pub struct ConstProp {
+    args : Vec<String>,
+}
+
+impl ConstProp {
+    pub fn new(args : Vec<String>) -> Self {
+        ConstProp {args}
+    }
+}
+
+impl Dataflow for ConstProp {
+    type Domain = ConstEnv;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn top(&self, cfg : &Cfg) -> Self::Domain {
+        let mut env = self.boundary(cfg);
+        for block in cfg.block_map.values() {
+            for code in &block.instrs {
+                if let AbstractCode::Instruction(instr) = code {
+                    if let Some(dest) = get_dest(instr) {
+                        env.insert(dest.to_string(), ConstValue::Overdefined);
+                    }
+                }
+            }
+        }
+        env
+    }
+
+    fn bottom(&self, _cfg : &Cfg) -> Self::Domain {
+        HashMap::new()
+    }
+
+    fn boundary(&self, _cfg : &Cfg) -> Self::Domain {
+        self.args.iter().map(|a| (a.to_string(), ConstValue::Overdefined)).collect()
+    }
+
+    fn transfer(&self, _point : ProgramPoint, instr : &AbstractInstruction,
+        in_b : &Self::Domain) -> Self::Domain {
+        let mut env = in_b.clone();
+        match instr {
+            AbstractInstruction::Constant {dest, value, ..} => {
+                env.insert(dest.to_string(), ConstValue::Const(value.clone()));
+            },
+            AbstractInstruction::Value {dest, op, args, ..} => {
+                let val = if is_foldable(op) {
+                    let vals : Vec<ConstValue> = args.iter().map(|a| lookup(in_b, a)).collect();
+                    eval_value(op, &vals)
+                } else {
+                    ConstValue::Overdefined
+                };
+                match val {
+                    ConstValue::Undefined => env.remove(dest),
+                    val => env.insert(dest.to_string(), val),
+                };
+            },
+            AbstractInstruction::Effect {..} => (),
+        }
+        env
+    }
+}
+
+fn const_instr(dest : &str, op_type : &Option<bril_rs::AbstractType>, value : Literal) -> AbstractCode {
+    AbstractCode::Instruction(AbstractInstruction::Constant {
+        dest : dest.to_string(),
+        op : ConstOps::Const,
+        const_type : op_type.clone(),
+        value,
+    })
+}
+
+fn jmp_instr(label : &str) -> AbstractCode {
+    AbstractCode::Instruction(AbstractInstruction::Effect {
+        op : "jmp".to_string(),
+        args : vec![],
+        funcs : vec![],
+        labels : vec![label.to_string()],
+    })
+}
+
+/// Rewrites the instructions of `cfg` whose results are constant into
+/// `const` and branches on constant conditions into `jmp`. Returns whether
+/// anything changed.
+pub fn const_prop_cfg(cfg : &mut Cfg, args : Vec<String>) -> bool {
+    let df = ConstProp::new(args);
+    let result = df_analysis(cfg, &df);
+    let mut changed = false;
+    let mut removed_edges = vec![];
+
+    let nums : Vec<i32> = cfg.block_map.keys().copied().collect();
+    for num in nums {
+        let mut env = result.block_in(num).clone();
+        let block = cfg.block_map.get_mut(&num).unwrap();
+        for i in 0..block.instrs.len() {
+            let instr = match &block.instrs[i] {
+                AbstractCode::Instruction(instr) => instr.clone(),
+                AbstractCode::Label {..} => continue,
+            };
+            match &instr {
+                AbstractInstruction::Value {dest, op_type, ..} => {
+                    let out = df.transfer(ProgramPoint::new(num, i), &instr, &env);
+                    if let ConstValue::Const(c) = lookup(&out, dest) {
+                        block.instrs[i] = const_instr(dest, op_type, c);
+                        changed = true;
+                    }
+                },
+                AbstractInstruction::Effect {op, args, labels, ..} if op == "br" => {
+                    if let ConstValue::Const(Literal::Bool(b)) = lookup(&env, &args[0]) {
+                        let (taken, other) = if b {
+                            (&labels[0], &labels[1])
+                        } else {
+                            (&labels[1], &labels[0])
+                        };
+                        if taken != other {
+                            removed_edges.push((num, other.to_string()));
+                        }
+                        block.instrs[i] = jmp_instr(taken);
+                        changed = true;
+                    }
+                },
+                _ => (),
+            }
+            env = df.transfer(ProgramPoint::new(num, i), &instr, &env);
+        }
+    }
+
+    for (num, label) in removed_edges {
+        let s = *cfg.name_map.get_by_right(&label).unwrap();
+        cfg.remove_edge(num, s);
+    }
+    changed
+}
+
+/// Global constant propagation: folds every instruction whose value is the
+/// same constant along all paths and resolves branches on constants.
+pub fn const_prop(func : &mut AbstractFunction) -> bool {
+    let mut cfg = form_cfg(form_blocks(func));
+    let args = func.args.iter().map(|a| a.name.clone()).collect();
+    let changed = const_prop_cfg(&mut cfg, args);
+    func.instrs = reassemble(cfg);
+    changed
+}
+
@@ -0,0 +1,64 @@
+use crate::cfg::Cfg;
+use crate::dominators::{form_post_dom_tree, DomTree};
+use std::collections::HashMap;
+
+/// Control dependence graph. Block `b` is control dependent on block `a` when
+/// `a` has an edge to a block that `b` post-dominates while `b` does not
+/// strictly post-dominate `a`, so the branch at the end of `a` decides
+/// whether `b` runs. Blocks that run whenever the function is entered have
+/// no dependences.
+#[derive(Debug, Default)]
+pub struct ControlDeps {
+    /// Branching blocks each block is control dependent on.
+    pub deps : HashMap<i32, Vec<i32>>,
+    /// Blocks that are control dependent on each block.
+    pub dependents : HashMap<i32, Vec<i32>>,
+    pub post_dom_tree : DomTree,
+}
+
+impl ControlDeps {
+    pub fn deps(&self, block : &i32) -> &[i32] {
+        self.deps.get(block).map(|d| &d[..]).unwrap_or(&[])
+    }
+
+    pub fn dependents(&self, block : &i32) -> &[i32] {
+        self.dependents.get(block).map(|d| &d[..]).unwrap_or(&[])
+    }
+}
+
+/// Builds the control dependence graph of Ferrante, Ottenstein and Warren:
+/// for every edge `a -> b` where `b` does not post-dominate `a`, the blocks
+/// on the post-dominator tree path from `b` up to, but excluding, the
+/// immediate post-dominator of `a` are control dependent on `a`.
+pub fn control_dependence(cfg : &Cfg) -> ControlDeps {
+    let tree = form_post_dom_tree(cfg);
+    let mut deps : HashMap<i32, Vec<i32>> = HashMap::new();
+    let mut dependents : HashMap<i32, Vec<i32>> = HashMap::new();
+    for v in cfg.block_map.keys() {
+        deps.insert(*v, vec![]);
+        dependents.insert(*v, vec![]);
+    }
+
+    let mut blocks : Vec<i32> = cfg.block_map.keys().copied().collect();
+    blocks.sort();
+    for a in blocks {
+        let a_ipdom = tree.idom(&a);
+        for b in cfg.succ.get(&a).unwrap() {
+            if tree.strictly_dominates(b, &a) {
+                continue;
+            }
+            let mut runner = Some(*b);
+            while let Some(r) = runner {
+                if Some(r) == a_ipdom {
+                    break;
+                }
+                if !deps.get(&r).unwrap().contains(&a) {
+                    deps.get_mut(&r).unwrap().push(a);
+                    dependents.get_mut(&a).unwrap().push(r);
+                }
+                runner = tree.idom(&r);
+            }
+        }
+    }
+    ControlDeps {deps, dependents, post_dom_tree : tree}
+}
@@ -1,209 +1,680 @@
 use bril_rs::{AbstractInstruction, AbstractCode};
 
-use crate::form_blocks::Block;
+use crate::bitset::BitSet;
+use crate::form_blocks::{Block, get_args, get_dest};
 use crate::cfg::Cfg;
-use std::collections::{HashSet, HashMap};
+use crate::dominators::get_reverse_post_order;
+use std::collections::{HashSet, HashMap, VecDeque};
+use std::fmt;
 use std::hash::Hash;
+use indexmap::IndexMap;
+use serde::Serialize;
 
-trait Dataflow {
-    type Item;
+/// A lattice of dataflow facts. `join` is the least upper bound, `meet` the
+/// greatest lower bound and `leq` the partial order between two elements.
+pub trait Lattice : Clone + PartialEq {
+    fn join(&self, other : &Self) -> Self;
 
-    fn merge(&self, sets : impl Iterator<Item=HashSet<Self::Item>>) -> HashSet<Self::Item>;
+    fn meet(&self, other : &Self) -> Self;
 
-    fn transfer(&self, b : &Block, in_b : &HashSet<Self::Item>) -> HashSet<Self::Item>;
+    fn leq(&self, other : &Self) -> bool {
+        &self.join(other) == other
+    }
+}
 
-    fn is_reverse(&self) -> bool;
+/// The powerset lattice, ordered by inclusion.
+impl<T> Lattice for HashSet<T>
+    where
+    T : Eq + Hash + Clone {
+    fn join(&self, other : &Self) -> Self {
+        self.union(other).cloned().collect()
+    }
+
+    fn meet(&self, other : &Self) -> Self {
+        self.intersection(other).cloned().collect()
+    }
 
-    fn init(&self) -> HashSet<Self::Item>;
+    fn leq(&self, other : &Self) -> bool {
+        self.is_subset(other)
+    }
 }
 
-fn union<T>(sets : impl Iterator<Item=HashSet<T>>) -> HashSet<T>
+impl Lattice for BitSet {
+    fn join(&self, other : &Self) -> Self {
+        let mut set = self.clone();
+        set.union_with(other);
+        set
+    }
+
+    fn meet(&self, other : &Self) -> Self {
+        let mut set = self.clone();
+        set.intersect_with(other);
+        set
+    }
+
+    fn leq(&self, other : &Self) -> bool {
+        self.is_subset(other)
+    }
+}
+
+/// Maps ordered pointwise, with a missing key standing for the bottom element
+/// of the value lattice.
+impl<K, V> Lattice for HashMap<K, V>
     where
-    T : Eq + Hash + Clone {
-    sets.into_iter().fold(HashSet::new(), |mut acc, p| {acc.extend(p); acc})
+    K : Eq + Hash + Clone,
+    V : Lattice {
+    fn join(&self, other : &Self) -> Self {
+        let mut map = self.clone();
+        for (k, v) in other {
+            let joined = match map.get(k) {
+                Some(w) => w.join(v),
+                None => v.clone(),
+            };
+            map.insert(k.clone(), joined);
+        }
+        map
+    }
+
+    fn meet(&self, other : &Self) -> Self {
+        let mut map = HashMap::new();
+        for (k, v) in self {
+            if let Some(w) = other.get(k) {
+                map.insert(k.clone(), v.meet(w));
+            }
+        }
+        map
+    }
+
+    fn leq(&self, other : &Self) -> bool {
+        self.iter().all(|(k, v)| match other.get(k) {
+            Some(w) => v.leq(w),
+            None => false,
+        })
+    }
 }
 
-fn df_analysis<T>(cfg : &Cfg, df : impl Dataflow<Item=T>) 
-    -> (HashMap<i32, HashSet<T>>, HashMap<i32, HashSet<T>>)
-    where T : Eq + Hash + Clone {
-    let forward = !df.is_reverse();
-    let mut in_map : HashMap<i32, HashSet<T>> = HashMap::new();
-    let mut out_map : HashMap<i32, HashSet<T>> = HashMap::new();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
 
-    let pred;
-    let succ;
+/// How facts from several predecessors (or successors, for backward
+/// analyses) are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confluence {
+    Join,
+    Meet,
+}
 
-    if forward {
-        pred = &cfg.pred;
-        succ = &cfg.succ;
-    } else {
-        succ = &cfg.pred;
-        pred = &cfg.succ;
+/// A position in a function: `index` is the position in `block.instrs`, and
+/// the point is located just before that instruction. An index equal to the
+/// length of the block is the end of the block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProgramPoint {
+    pub block : i32,
+    pub index : usize,
+}
+
+impl ProgramPoint {
+    pub fn new(block : i32, index : usize) -> Self {
+        ProgramPoint {block, index}
     }
+}
 
-    if forward {
-        let (num, _) = cfg.block_map.get_index(0).unwrap();
-        in_map.insert(*num, df.init());
-    } else {
-        let (num, _) = cfg.block_map.last().unwrap();
-        in_map.insert(*num, df.init());
+/// A dataflow analysis over a `Cfg`. Implementors provide the lattice bounds
+/// and a per-instruction transfer function; the per-block transfer function
+/// defaults to folding the instruction transfer over the block in the
+/// direction of the analysis.
+pub trait Dataflow {
+    type Domain : Lattice;
+
+    fn direction(&self) -> Direction;
+
+    fn top(&self, cfg : &Cfg) -> Self::Domain;
+
+    fn bottom(&self, cfg : &Cfg) -> Self::Domain;
+
+    fn confluence(&self) -> Confluence {
+        Confluence::Join
     }
 
-    for (num, _) in &cfg.block_map {
-        out_map.insert(*num, df.init());
+    /// Value at the entry block (forward) or at the exit blocks (backward).
+    fn boundary(&self, cfg : &Cfg) -> Self::Domain {
+        self.init(cfg)
     }
 
-    let mut worklist = Vec::new();
-    worklist.extend(cfg.block_map.keys());
+    /// Value every other point starts from before iteration.
+    fn init(&self, cfg : &Cfg) -> Self::Domain {
+        match self.confluence() {
+            Confluence::Join => self.bottom(cfg),
+            Confluence::Meet => self.top(cfg),
+        }
+    }
 
-    while !worklist.is_empty() {
-        let num = worklist.pop().unwrap();
-        let b = cfg.block_map.get(num).unwrap();
-        let preds = pred.get(num).unwrap();
-        let out_p : Vec<HashSet<T>>
-            = preds.into_iter().map(|p| out_map.get(p).unwrap().clone()).collect();
-        let in_b = df.merge(out_p.into_iter());
-        in_map.insert(*num, in_b);
-        let out_b = df.transfer(b, in_map.get(num).unwrap());
-        if out_map.get(num).unwrap() != &out_b {
-            worklist.extend(succ.get(num).unwrap());
+    fn merge(&self, a : &Self::Domain, b : &Self::Domain) -> Self::Domain {
+        match self.confluence() {
+            Confluence::Join => a.join(b),
+            Confluence::Meet => a.meet(b),
         }
-        out_map.insert(*num, out_b);
     }
-    if forward {
-        (in_map, out_map)
-    } else {
-        (out_map, in_map)
+
+    fn transfer(&self, point : ProgramPoint, instr : &AbstractInstruction,
+        val : &Self::Domain) -> Self::Domain;
+
+    fn transfer_block(&self, num : i32, block : &Block, val : &Self::Domain) -> Self::Domain {
+        let mut val = val.clone();
+        let instrs : Vec<_> = block.instrs.iter().enumerate().collect();
+        let instrs : Box<dyn Iterator<Item=&(usize, &AbstractCode)>> = match self.direction() {
+            Direction::Forward => Box::new(instrs.iter()),
+            Direction::Backward => Box::new(instrs.iter().rev()),
+        };
+        for (i, code) in instrs {
+            if let AbstractCode::Instruction(instr) = code {
+                val = self.transfer(ProgramPoint::new(num, *i), instr, &val);
+            }
+        }
+        val
+    }
+}
+
+/// Solution of a dataflow analysis. `in_map` holds the value at the start of
+/// each block and `out_map` the value at its end, in program order for both
+/// directions.
+#[derive(Debug, Clone)]
+pub struct DfResult<D> {
+    pub in_map : HashMap<i32, D>,
+    pub out_map : HashMap<i32, D>,
+}
+
+impl<D : Lattice> DfResult<D> {
+    pub fn block_in(&self, num : i32) -> &D {
+        self.in_map.get(&num).unwrap()
     }
+
+    pub fn block_out(&self, num : i32) -> &D {
+        self.out_map.get(&num).unwrap()
+    }
+
+    /// Value just before the instruction at `point`.
+    pub fn before(&self, cfg : &Cfg, df : &impl Dataflow<Domain=D>, point : ProgramPoint) -> D {
+        let block = cfg.block_map.get(&point.block).unwrap();
+        match df.direction() {
+            Direction::Forward => {
+                let mut val = self.block_in(point.block).clone();
+                for (i, code) in block.instrs.iter().enumerate().take(point.index) {
+                    if let AbstractCode::Instruction(instr) = code {
+                        val = df.transfer(ProgramPoint::new(point.block, i), instr, &val);
+                    }
+                }
+                val
+            },
+            Direction::Backward => {
+                let mut val = self.block_out(point.block).clone();
+                for (i, code) in block.instrs.iter().enumerate().skip(point.index).rev() {
+                    if let AbstractCode::Instruction(instr) = code {
+                        val = df.transfer(ProgramPoint::new(point.block, i), instr, &val);
+                    }
+                }
+                val
+            },
+        }
+    }
+
+    /// Value just after the instruction at `point`.
+    pub fn after(&self, cfg : &Cfg, df : &impl Dataflow<Domain=D>, point : ProgramPoint) -> D {
+        self.before(cfg, df, ProgramPoint::new(point.block, point.index + 1))
+    }
+}
+
+/// Blocks in an order that visits most predecessors first for forward
+/// analyses and most successors first for backward ones. Unreachable blocks
+/// are appended at the end.
+fn iteration_order(cfg : &Cfg, direction : Direction) -> Vec<i32> {
+    let mut order = get_reverse_post_order(cfg);
+    if direction == Direction::Backward {
+        order.reverse();
+    }
+    let visited : HashSet<i32> = order.iter().copied().collect();
+    order.extend(cfg.block_map.keys().filter(|n| !visited.contains(n)));
+    order
 }
 
-struct DefinedVars;
+pub fn df_analysis<A : Dataflow>(cfg : &Cfg, df : &A) -> DfResult<A::Domain> {
+    let forward = df.direction() == Direction::Forward;
+    let (pred, succ) = if forward {
+        (&cfg.pred, &cfg.succ)
+    } else {
+        (&cfg.succ, &cfg.pred)
+    };
+
+    let entry = cfg.block_map.first().map(|(num, _)| *num);
+    let is_boundary = |num : i32| {
+        if forward {
+            Some(num) == entry
+        } else {
+            pred.get(&num).unwrap().is_empty()
+        }
+    };
+
+    // Values on the incoming and outgoing sides of each block with respect
+    // to the direction of the analysis.
+    let mut before_map : HashMap<i32, A::Domain> = HashMap::new();
+    let mut after_map : HashMap<i32, A::Domain> = HashMap::new();
+    for num in cfg.block_map.keys() {
+        after_map.insert(*num, df.init(cfg));
+    }
+
+    let mut worklist : VecDeque<i32> = iteration_order(cfg, df.direction()).into();
+    let mut queued : HashSet<i32> = worklist.iter().copied().collect();
 
-impl DefinedVars {
-    fn get_def_vars(&self, b : &Block) -> HashSet<String> {
-        let mut set = HashSet::new();
-        for instr in &b.instrs {
-            if let AbstractCode::Instruction(instr) = instr {
-                if let AbstractInstruction::Constant {dest, ..}
-                | AbstractInstruction::Value {dest, ..} = instr {
-                    set.insert(dest.to_string());
+    while let Some(num) = worklist.pop_front() {
+        queued.remove(&num);
+        let b = cfg.block_map.get(&num).unwrap();
+        let mut val = if is_boundary(num) {
+            df.boundary(cfg)
+        } else {
+            df.init(cfg)
+        };
+        let mut first = !is_boundary(num);
+        for p in pred.get(&num).unwrap() {
+            let out_p = after_map.get(p).unwrap();
+            if first {
+                val = out_p.clone();
+                first = false;
+            } else {
+                val = df.merge(&val, out_p);
+            }
+        }
+        let out_b = df.transfer_block(num, b, &val);
+        before_map.insert(num, val);
+        if after_map.get(&num).unwrap() != &out_b {
+            for s in succ.get(&num).unwrap() {
+                if queued.insert(*s) {
+                    worklist.push_back(*s);
                 }
             }
         }
-        set
+        after_map.insert(num, out_b);
+    }
+
+    if forward {
+        DfResult {in_map : before_map, out_map : after_map}
+    } else {
+        DfResult {in_map : after_map, out_map : before_map}
     }
 }
 
-impl Dataflow for DefinedVars {
-    type Item = String;
+/// The in and out sets of one block, sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BlockFacts {
+    #[serde(rename = "in")]
+    pub ins : Vec<String>,
+    #[serde(rename = "out")]
+    pub outs : Vec<String>,
+}
+
+/// The result of a set-valued analysis for every block, keyed by block name
+/// in cfg order. Serializes as `{"block": {"in": [...], "out": [...]}}`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct DfReport {
+    pub blocks : IndexMap<String, BlockFacts>,
+}
+
+impl DfReport {
+    pub fn new(cfg : &Cfg, result : DfResult<HashSet<String>>) -> Self {
+        let DfResult {mut in_map, mut out_map} = result;
+        let mut blocks = IndexMap::new();
+        for num in cfg.block_map.keys() {
+            let name = cfg.name_map.get_by_left(num).unwrap();
+            let mut ins : Vec<String> = in_map.remove(num).unwrap().into_iter().collect();
+            let mut outs : Vec<String> = out_map.remove(num).unwrap().into_iter().collect();
+            ins.sort();
+            outs.sort();
+            blocks.insert(name.clone(), BlockFacts {ins, outs});
+        }
+        DfReport {blocks}
+    }
+}
 
-    fn merge(&self, sets : impl Iterator<Item=HashSet<Self::Item>>) -> HashSet<Self::Item> {
-        union(sets)
+impl fmt::Display for DfReport {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        for (name, facts) in &self.blocks {
+            writeln!(f, "{name}:")?;
+            writeln!(f, "    in: {:?}", facts.ins)?;
+            writeln!(f, "    out: {:?}", facts.outs)?;
+        }
+        Ok(())
     }
+}
 
-    fn transfer(&self, b : &Block, in_b : &HashSet<Self::Item>) -> HashSet<Self::Item> {
-        let mut set : HashSet<String> = in_b.clone();
-        set.extend(self.get_def_vars(b));
-        set
+/// Variables that have been assigned on some path to each point.
+pub struct DefinedVars;
+
+impl Dataflow for DefinedVars {
+    type Domain = HashSet<String>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
     }
 
-    fn is_reverse(&self) -> bool {
-        false
+    fn top(&self, cfg : &Cfg) -> Self::Domain {
+        all_dests(cfg)
     }
 
-    fn init(&self) -> HashSet<Self::Item> {
+    fn bottom(&self, _cfg : &Cfg) -> Self::Domain {
         HashSet::new()
     }
+
+    fn transfer(&self, _point : ProgramPoint, instr : &AbstractInstruction,
+        in_b : &Self::Domain) -> Self::Domain {
+        let mut set = in_b.clone();
+        if let Some(dest) = get_dest(instr) {
+            set.insert(dest.to_string());
+        }
+        set
+    }
 }
 
-pub fn declared_vars(cfg : &Cfg) {
-    let (mut in_map, mut out_map) = df_analysis(cfg, DefinedVars);
-    for num in cfg.block_map.keys() {
-        let name = cfg.name_map.get_by_left(num).unwrap();
-        let mut ins : Vec<String> = in_map.remove(num).unwrap().drain().collect();
-        let mut outs : Vec<String> = out_map.remove(num).unwrap().drain().collect();
-        ins.sort();
-        outs.sort();
-        println!("{name}:");
-        println!("    in: {ins:?}");
-        println!("    out: {outs:?}");
-    }
-}
-
-struct LiveVars;
-
-impl LiveVars {
-    fn get_def_vars(&self, b : &Block) -> HashSet<String> {
-        let mut set = HashSet::new();
-        for instr in &b.instrs {
-            if let AbstractCode::Instruction(instr) = instr {
-                if let AbstractInstruction::Constant {dest, ..}
-                | AbstractInstruction::Value {dest, ..} = instr {
-                    set.insert(dest.to_string());
+pub fn declared_vars(cfg : &Cfg) -> DfReport {
+    DfReport::new(cfg, df_analysis(cfg, &DefinedVars))
+}
+
+/// Variables whose current value may be read later on some path.
+pub struct LiveVars;
+
+impl Dataflow for LiveVars {
+    type Domain = HashSet<String>;
+
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+
+    fn top(&self, cfg : &Cfg) -> Self::Domain {
+        let mut vars = all_dests(cfg);
+        for block in cfg.block_map.values() {
+            for code in &block.instrs {
+                if let AbstractCode::Instruction(instr) = code {
+                    vars.extend(get_args(instr).iter().cloned());
                 }
             }
         }
+        vars
+    }
+
+    fn bottom(&self, _cfg : &Cfg) -> Self::Domain {
+        HashSet::new()
+    }
+
+    fn transfer(&self, _point : ProgramPoint, instr : &AbstractInstruction,
+        out_b : &Self::Domain) -> Self::Domain {
+        let mut set = out_b.clone();
+        if let Some(dest) = get_dest(instr) {
+            set.remove(dest);
+        }
+        set.extend(get_args(instr).iter().cloned());
         set
     }
+}
+
+/// Live variables in SSA form. A phi reads each argument at the end of the
+/// predecessor its label names rather than at the phi itself, so a variable
+/// that only flows into a phi is live out of that one predecessor and not
+/// into the phi's block.
+pub struct PhiLiveVars {
+    edge_uses : HashMap<i32, Vec<String>>,
+}
 
-    fn get_uses(&self, b : &Block) -> HashSet<String> {
-        let mut defined = HashSet::new();
-        let mut used = HashSet::new();
-        for instr in &b.instrs{
-            if let AbstractCode::Instruction(instr) = instr {
-                if let AbstractInstruction::Value {args, ..}
-                | AbstractInstruction::Effect {args, ..} = instr {
-                    for a in args {
-                        if !defined.contains(a) {
-                            used.insert(a.to_string());
+impl PhiLiveVars {
+    pub fn new(cfg : &Cfg) -> Self {
+        let mut edge_uses : HashMap<i32, Vec<String>> = HashMap::new();
+        for block in cfg.block_map.values() {
+            for code in &block.instrs {
+                if let AbstractCode::Instruction(AbstractInstruction::Value {op, args, labels, ..}) = code {
+                    if op == "phi" {
+                        for (a, l) in args.iter().zip(labels) {
+                            if let Some(pred) = cfg.name_map.get_by_right(l) {
+                                edge_uses.entry(*pred).or_default().push(a.clone());
+                            }
                         }
                     }
                 }
-                if let AbstractInstruction::Value {dest, ..}
-                | AbstractInstruction::Constant {dest, ..} = instr {
-                    defined.insert(dest);
-                }
             }
         }
-        used
+        PhiLiveVars {edge_uses}
     }
 }
 
-impl Dataflow for LiveVars {
-    type Item = String;
+impl Dataflow for PhiLiveVars {
+    type Domain = HashSet<String>;
 
-    fn merge(&self, sets : impl Iterator<Item=HashSet<Self::Item>>) -> HashSet<Self::Item> {
-        union(sets)
+    fn direction(&self) -> Direction {
+        Direction::Backward
     }
 
-    fn transfer(&self, b : &Block, out_b : &HashSet<Self::Item>) -> HashSet<Self::Item> {
-        let mut set : HashSet<String> = HashSet::new();
-        set.extend(self.get_uses(b));
-        let diff : HashSet<String> 
-            = out_b.clone().difference(&self.get_def_vars(b)).map(|s| s.to_string()).collect();
-        set.extend(diff);
+    fn top(&self, cfg : &Cfg) -> Self::Domain {
+        LiveVars.top(cfg)
+    }
+
+    fn bottom(&self, _cfg : &Cfg) -> Self::Domain {
+        HashSet::new()
+    }
+
+    fn transfer(&self, _point : ProgramPoint, instr : &AbstractInstruction,
+        out_b : &Self::Domain) -> Self::Domain {
+        let mut set = out_b.clone();
+        if let Some(dest) = get_dest(instr) {
+            set.remove(dest);
+        }
+        match instr {
+            AbstractInstruction::Value {op, ..} if op == "phi" => (),
+            _ => set.extend(get_args(instr).iter().cloned()),
+        }
         set
     }
 
-    fn is_reverse(&self) -> bool {
-        true
+    /// Adds the phi arguments read along the block's outgoing edges before
+    /// running the instructions backwards.
+    fn transfer_block(&self, num : i32, block : &Block, out_b : &Self::Domain) -> Self::Domain {
+        let mut val = out_b.clone();
+        val.extend(self.edge_uses.get(&num).into_iter().flatten().cloned());
+        for (i, code) in block.instrs.iter().enumerate().rev() {
+            if let AbstractCode::Instruction(instr) = code {
+                val = self.transfer(ProgramPoint::new(num, i), instr, &val);
+            }
+        }
+        val
     }
+}
 
-    fn init(&self) -> HashSet<Self::Item> {
-        HashSet::new()
+pub fn live_vars(cfg : &Cfg) -> DfReport {
+    DfReport::new(cfg, df_analysis(cfg, &LiveVars))
+}
+
+/// A definition of `var`, either by the instruction at `point` or, when
+/// `point` is `None`, by a function argument.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Def {
+    pub var : String,
+    pub point : Option<ProgramPoint>,
+}
+
+impl Def {
+    pub fn new(var : String, point : Option<ProgramPoint>) -> Self {
+        Def {var, point}
     }
 }
 
-pub fn live_vars(cfg : &Cfg) {
-    let (mut in_map, mut out_map) = df_analysis(cfg, LiveVars);
-    for num in cfg.block_map.keys() {
-        let name = cfg.name_map.get_by_left(num).unwrap();
-        let mut ins : Vec<String> = in_map.remove(num).unwrap().drain().collect();
-        let mut outs : Vec<String> = out_map.remove(num).unwrap().drain().collect();
-        ins.sort();
-        outs.sort();
-        println!("{name}:");
-        println!("    in: {ins:?}");
-        println!("    out: {outs:?}");
+/// The definitions of a function, numbered so that reaching definitions can
+/// be kept in a `BitSet`. Arguments come first.
+struct DefNumbering {
+    defs : Vec<Def>,
+    num_args : usize,
+    by_var : HashMap<String, Vec<usize>>,
+    by_point : HashMap<ProgramPoint, usize>,
+}
+
+impl DefNumbering {
+    fn new(cfg : &Cfg, args : &[String]) -> Self {
+        let mut numbering = DefNumbering {defs : vec![], num_args : args.len(), by_var : HashMap::new(), by_point : HashMap::new()};
+        for a in args {
+            numbering.by_var.entry(a.to_string()).or_default().push(numbering.defs.len());
+            numbering.defs.push(Def::new(a.to_string(), None));
+        }
+        for (num, block) in &cfg.block_map {
+            for (i, code) in block.instrs.iter().enumerate() {
+                if let AbstractCode::Instruction(instr) = code {
+                    if let Some(dest) = get_dest(instr) {
+                        let point = ProgramPoint::new(*num, i);
+                        numbering.by_var.entry(dest.to_string()).or_default().push(numbering.defs.len());
+                        numbering.by_point.insert(point, numbering.defs.len());
+                        numbering.defs.push(Def::new(dest.to_string(), Some(point)));
+                    }
+                }
+            }
+        }
+        numbering
+    }
+
+    fn kill_gen(&self, point : ProgramPoint, instr : &AbstractInstruction, reaching : &mut BitSet) {
+        if let Some(dest) = get_dest(instr) {
+            for d in &self.by_var[dest.as_str()] {
+                reaching.remove(*d);
+            }
+            reaching.insert(self.by_point[&point]);
+        }
+    }
+}
+
+impl Dataflow for DefNumbering {
+    type Domain = BitSet;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn top(&self, _cfg : &Cfg) -> Self::Domain {
+        let mut all = BitSet::new(self.defs.len());
+        for d in 0..self.defs.len() {
+            all.insert(d);
+        }
+        all
+    }
+
+    fn bottom(&self, _cfg : &Cfg) -> Self::Domain {
+        BitSet::new(self.defs.len())
+    }
+
+    fn boundary(&self, _cfg : &Cfg) -> Self::Domain {
+        let mut args = BitSet::new(self.defs.len());
+        for d in 0..self.num_args {
+            args.insert(d);
+        }
+        args
+    }
+
+    fn transfer(&self, point : ProgramPoint, instr : &AbstractInstruction,
+        in_b : &Self::Domain) -> Self::Domain {
+        let mut reaching = in_b.clone();
+        self.kill_gen(point, instr, &mut reaching);
+        reaching
+    }
+
+    fn transfer_block(&self, num : i32, block : &Block, in_b : &Self::Domain) -> Self::Domain {
+        let mut reaching = in_b.clone();
+        for (i, code) in block.instrs.iter().enumerate() {
+            if let AbstractCode::Instruction(instr) = code {
+                self.kill_gen(ProgramPoint::new(num, i), instr, &mut reaching);
+            }
+        }
+        reaching
+    }
+}
+
+/// Definitions that may reach each point without being overwritten. Every
+/// definition is identified by the block and instruction index that makes
+/// it, or is one of the function's arguments. Sets are kept internally as
+/// `BitSet`s over numbered definitions; `set_at`, `step` and `defs_in` let a
+/// caller walk a block without rebuilding `Def`s at every point.
+pub struct ReachingDefs {
+    numbering : DefNumbering,
+    result : DfResult<BitSet>,
+}
+
+impl ReachingDefs {
+    pub fn new(cfg : &Cfg, args : &[String]) -> Self {
+        let numbering = DefNumbering::new(cfg, args);
+        let result = df_analysis(cfg, &numbering);
+        ReachingDefs {numbering, result}
+    }
+
+    /// Every definition in the function, arguments first.
+    pub fn defs(&self) -> &[Def] {
+        &self.numbering.defs
+    }
+
+    /// The definitions reaching the point just before `point`, in the order
+    /// of `defs`.
+    pub fn at(&self, cfg : &Cfg, point : ProgramPoint) -> Vec<Def> {
+        self.defs_in(&self.set_at(cfg, point))
+    }
+
+    /// The definitions of `var` reaching `point`.
+    pub fn of_var_at(&self, cfg : &Cfg, var : &str, point : ProgramPoint) -> Vec<Def> {
+        self.var_defs_in(var, &self.set_at(cfg, point))
+    }
+
+    pub fn block_in(&self, num : i32) -> Vec<Def> {
+        self.defs_in(self.result.block_in(num))
+    }
+
+    pub fn block_out(&self, num : i32) -> Vec<Def> {
+        self.defs_in(self.result.block_out(num))
+    }
+
+    /// The numbered set reaching `point`.
+    pub fn set_at(&self, cfg : &Cfg, point : ProgramPoint) -> BitSet {
+        self.result.before(cfg, &self.numbering, point)
+    }
+
+    pub fn block_in_set(&self, num : i32) -> &BitSet {
+        self.result.block_in(num)
+    }
+
+    pub fn block_out_set(&self, num : i32) -> &BitSet {
+        self.result.block_out(num)
+    }
+
+    /// Updates `set` from the point before the instruction at `point` to the
+    /// point after it.
+    pub fn step(&self, point : ProgramPoint, instr : &AbstractInstruction, set : &mut BitSet) {
+        self.numbering.kill_gen(point, instr, set);
+    }
+
+    pub fn defs_in(&self, set : &BitSet) -> Vec<Def> {
+        set.iter().map(|d| self.numbering.defs[d].clone()).collect()
+    }
+
+    /// The definitions of `var` in `set`.
+    pub fn var_defs_in(&self, var : &str, set : &BitSet) -> Vec<Def> {
+        match self.numbering.by_var.get(var) {
+            Some(ds) => ds.iter().filter(|d| set.contains(**d)).map(|d| self.numbering.defs[*d].clone()).collect(),
+            None => vec![],
+        }
+    }
+}
+
+fn all_dests(cfg : &Cfg) -> HashSet<String> {
+    let mut vars = HashSet::new();
+    for block in cfg.block_map.values() {
+        for code in &block.instrs {
+            if let AbstractCode::Instruction(instr) = code {
+                if let Some(dest) = get_dest(instr) {
+                    vars.insert(dest.to_string());
+                }
+            }
+        }
     }
+    vars
 }
@@ -1,113 +1,125 @@
 use crate::cfg::*;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
+use indexmap::IndexMap;
+use serde::Serialize;
 
-fn post_order_rec(cfg : &Cfg, node : i32, current : &mut Vec<i32>, visited : &mut HashSet<i32>) {
-    visited.insert(node);
-    let succ = cfg.succ.get(&node).unwrap();
-    for s in succ {
-        if !visited.contains(s) {
-            post_order_rec(cfg, *s, current, visited);
+/// Post order of the nodes reachable from `entry`. The traversal uses an
+/// explicit stack so that long chains of blocks cannot overflow the call
+/// stack.
+fn post_order(succ : &HashMap<i32, Vec<i32>>, entry : i32, visited : &mut HashSet<i32>) -> Vec<i32> {
+    let mut order = vec![];
+    let mut stack : Vec<(i32, usize)> = vec![(entry, 0)];
+    visited.insert(entry);
+    while let Some((node, i)) = stack.pop() {
+        if let Some(s) = succ.get(&node).unwrap().get(i) {
+            stack.push((node, i + 1));
+            if visited.insert(*s) {
+                stack.push((*s, 0));
+            }
+        } else {
+            order.push(node);
         }
     }
-    current.push(node);
+    order
 }
 
-fn get_reverse_post_order(cfg : &Cfg) -> Vec<i32> {
-    let vertices = &mut vec![];
-    post_order_rec(cfg, *cfg.block_map.first().unwrap().0, vertices, &mut HashSet::new());
-    vertices.into_iter().rev().map(|a| *a).collect()
+pub fn get_reverse_post_order(cfg : &Cfg) -> Vec<i32> {
+    match cfg.block_map.first() {
+        Some((entry, _)) => post_order(&cfg.succ, *entry, &mut HashSet::new())
+            .into_iter().rev().collect(),
+        None => vec![],
+    }
 }
 
-pub fn find_dominators_num(cfg : &Cfg) -> HashMap<i32, HashSet<i32>> {
-    let mut dom : HashMap<i32, HashSet<i32>> = HashMap::new();
-    let all_blocks : HashSet<i32> = cfg.block_map.keys().copied().collect();
-    let (&first_num, _) = cfg.block_map.get_index(0).unwrap();
-    for num in cfg.block_map.keys() {
-        dom.insert(*num, all_blocks.clone());
-    }
-    let mut entry_blocks = HashSet::new();
-    entry_blocks.insert(first_num);
-    dom.insert(first_num, entry_blocks);
+/// Immediate dominators using the iterative algorithm of Cooper, Harvey and
+/// Kennedy, "A Simple, Fast Dominance Algorithm". Nodes are numbered by
+/// their position in reverse post order, so walking up the tree from two
+/// nodes until their numbers meet finds their nearest common dominator.
+/// Only the nodes in `rpo` get an entry; the root has no immediate dominator.
+fn immediate_doms(rpo : &[i32], pred : &HashMap<i32, Vec<i32>>) -> HashMap<i32, Option<i32>> {
+    let order : HashMap<i32, usize> = rpo.iter().enumerate().map(|(i, b)| (*b, i)).collect();
+    let mut idom : Vec<Option<usize>> = vec![None; rpo.len()];
+    if !rpo.is_empty() {
+        idom[0] = Some(0);
+    }
+
+    let intersect = |idom : &Vec<Option<usize>>, mut a : usize, mut b : usize| {
+        while a != b {
+            while a > b {
+                a = idom[a].unwrap();
+            }
+            while b > a {
+                b = idom[b].unwrap();
+            }
+        }
+        a
+    };
+
     let mut changed = true;
-    let vertices = get_reverse_post_order(cfg);
     while changed {
         changed = false;
-        for v in &vertices {
-            let preds = cfg.pred.get(v).unwrap();
-            let mut new_dom : HashSet<i32> = 
-                if preds.len() == 0 {
-                    HashSet::new()
-                } else {
-                    preds.into_iter().fold(all_blocks.clone(), 
-                    |acc, p| acc.intersection(dom.get(p).unwrap()).copied().collect())
+        for (i, v) in rpo.iter().enumerate().skip(1) {
+            let mut new_idom = None;
+            for p in pred.get(v).unwrap() {
+                let p = match order.get(p) {
+                    Some(p) if idom[*p].is_some() => *p,
+                    _ => continue,
+                };
+                new_idom = match new_idom {
+                    None => Some(p),
+                    Some(d) => Some(intersect(&idom, p, d)),
                 };
-            new_dom.insert(*v);
-            changed |= &new_dom != dom.get(v).unwrap();
-            dom.insert(*v, new_dom);
+            }
+            if idom[i] != new_idom {
+                idom[i] = new_idom;
+                changed = true;
+            }
         }
     }
-    dom
-}
-
-fn convert_doms_to_string(cfg : &Cfg, dom : HashMap<i32, HashSet<i32>>) -> HashMap<String, HashSet<String>> {
-    let mut dominators = HashMap::new();
-    for (v, doms) in dom {
-        let name = cfg.name_map.get_by_left(&v).unwrap().clone();
-        let name_doms = doms.into_iter().map(|d|
-            cfg.name_map.get_by_left(&d).unwrap().to_string()).collect();
-        dominators.insert(name, name_doms);
-    }
-    dominators
-}
-
-pub fn find_dominators(cfg : &Cfg) -> HashMap<String, HashSet<String>> {
-    let dom = find_dominators_num(cfg);
-    convert_doms_to_string(cfg, dom)
-}
 
-fn get_strict_doms(dom : &HashMap<i32, HashSet<i32>>) -> HashMap<i32, HashSet<i32>> {
-    let mut dom = dom.clone();
-    dom.iter_mut().for_each(|(v, d)| {d.remove(&v);});
-    dom
+    rpo.iter().enumerate().map(|(i, v)| {
+        let d = if i == 0 { None } else { idom[i].map(|d| rpo[d]) };
+        (*v, d)
+    }).collect()
 }
 
-fn find_immediate_doms(cfg : &Cfg) -> HashMap<i32, Option<i32>> {
-    let mut idom = HashMap::new();
-    let dom = find_dominators_num(cfg);
-    let sdom = get_strict_doms(&dom);
-    for (v, doms) in &sdom {
-        let mut v_idom = None;
-        for a in doms {
-            let mut imm = true;
-            for d in doms {
-                imm &= !sdom.get(d).unwrap().contains(a);
-            }
-            if imm {
-                v_idom = Some(*a);
-            }
-        }
-        idom.insert(*v, v_idom);
+/// Immediate dominator of every block. The entry and unreachable blocks have
+/// no immediate dominator.
+pub fn find_immediate_doms(cfg : &Cfg) -> HashMap<i32, Option<i32>> {
+    let mut idom = immediate_doms(&get_reverse_post_order(cfg), &cfg.pred);
+    for v in cfg.block_map.keys() {
+        idom.entry(*v).or_insert(None);
     }
     idom
 }
 
-#[derive(Default, Debug)]
+/// Serializes as `{"nodes": [...]}`, where `parent` and `children` are
+/// indices into `nodes`.
+#[derive(Default, Debug, Serialize)]
 pub struct DomTree {
+    #[serde(rename = "nodes")]
     arena : Vec<DomNode>,
+    #[serde(skip)]
     num_to_id : HashMap<i32, usize>,
+    #[serde(skip)]
+    pre : Vec<usize>,
+    #[serde(skip)]
+    post : Vec<usize>,
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize)]
 pub struct DomNode {
     pub idx : usize,
+    pub num : i32,
     pub label : String,
     pub parent : Option<usize>,
     pub children : Vec<usize>,
 }
 
 impl DomNode {
-    pub fn new(idx: usize, label: String) -> Self {
-        Self {idx, label, parent: None, children: vec![]}
+    pub fn new(idx: usize, num : i32, label: String) -> Self {
+        Self {idx, num, label, parent: None, children: vec![]}
     }
 }
 
@@ -115,7 +127,7 @@ impl DomTree {
     pub fn new_node(&mut self, label : String, num : i32) -> usize {
         let idx = self.arena.len();
         self.num_to_id.insert(num, idx);
-        self.arena.push(DomNode::new(idx, label));
+        self.arena.push(DomNode::new(idx, num, label));
         idx
     }
 
@@ -139,41 +151,149 @@ impl DomTree {
         let parent_node = self.arena.get_mut(parent_idx).unwrap();
         parent_node.children.push(child_idx);
     }
+
+    pub fn nodes(&self) -> &[DomNode] {
+        &self.arena
+    }
+
+    /// Block number of the immediate dominator of `v`.
+    pub fn idom(&self, v : &i32) -> Option<i32> {
+        let node = self.get_node(v)?;
+        node.parent.map(|p| self.arena[p].num)
+    }
+
+    /// Block numbers of the children of `v` in the tree.
+    pub fn children(&self, v : &i32) -> Vec<i32> {
+        match self.get_node(v) {
+            Some(node) => node.children.iter().map(|c| self.arena[*c].num).collect(),
+            None => vec![],
+        }
+    }
+
+    /// Numbers every node in a depth-first walk so that dominance queries
+    /// take constant time. Called after the last `add_child`.
+    pub fn number_nodes(&mut self) {
+        self.pre = vec![0; self.arena.len()];
+        self.post = vec![0; self.arena.len()];
+        let mut counter = 0;
+        let roots : Vec<usize> = self.arena.iter()
+            .filter(|n| n.parent.is_none()).map(|n| n.idx).collect();
+        for root in roots {
+            let mut stack = vec![(root, 0)];
+            self.pre[root] = counter;
+            counter += 1;
+            while let Some((node, i)) = stack.pop() {
+                if let Some(&c) = self.arena[node].children.get(i) {
+                    stack.push((node, i + 1));
+                    self.pre[c] = counter;
+                    counter += 1;
+                    stack.push((c, 0));
+                } else {
+                    self.post[node] = counter;
+                    counter += 1;
+                }
+            }
+        }
+    }
+
+    /// Whether block `a` dominates block `b`.
+    pub fn dominates(&self, a : &i32, b : &i32) -> bool {
+        match (self.get_num_id(a), self.get_num_id(b)) {
+            (Some(a), Some(b)) => self.pre[*a] <= self.pre[*b] && self.post[*b] <= self.post[*a],
+            _ => false,
+        }
+    }
+
+    pub fn strictly_dominates(&self, a : &i32, b : &i32) -> bool {
+        a != b && self.dominates(a, b)
+    }
 }
 
-pub fn form_dom_tree(cfg : &Cfg) -> DomTree {
-    let idom = find_immediate_doms(cfg);
+fn dom_tree_from_idoms(cfg : &Cfg, idom : &HashMap<i32, Option<i32>>) -> DomTree {
     let mut tree = DomTree::default();
-    let mut idom : Vec<_> = idom.into_iter().collect();
-    idom.sort_by_key(|(v,_)| *v);
-    for (v, _) in &idom {
-        let label = cfg.name_map.get_by_left(v).unwrap();
-        tree.new_node(label.to_string(), *v);
+    let mut nums : Vec<i32> = idom.keys().copied().collect();
+    nums.sort();
+    for v in &nums {
+        let label = match cfg.name_map.get_by_left(v) {
+            Some(label) => label.to_string(),
+            None => fresh("exit".to_string(), cfg.name_map.right_values()),
+        };
+        tree.new_node(label, *v);
     }
-    for (v, parent) in idom {
-        if let Some(p) = parent {
-            tree.add_child(&p, &v);
+    for v in &nums {
+        if let Some(p) = idom.get(v).unwrap() {
+            tree.add_child(p, v);
         }
     }
+    tree.number_nodes();
     tree
 }
 
-pub fn get_dominance_frontier_num(cfg : &Cfg) -> HashMap<i32, HashSet<i32>> {
+pub fn form_dom_tree(cfg : &Cfg) -> DomTree {
+    let idom = find_immediate_doms(cfg);
+    dom_tree_from_idoms(cfg, &idom)
+}
+
+/// Dominators of every block: the block itself and its ancestors in the
+/// dominator tree. An unreachable block is only dominated by itself.
+pub fn find_dominators_num(cfg : &Cfg) -> HashMap<i32, HashSet<i32>> {
+    let idom = find_immediate_doms(cfg);
+    let mut dom = HashMap::new();
+    for v in cfg.block_map.keys() {
+        let mut doms = HashSet::new();
+        let mut node = Some(*v);
+        while let Some(n) = node {
+            doms.insert(n);
+            node = *idom.get(&n).unwrap();
+        }
+        dom.insert(*v, doms);
+    }
+    dom
+}
+
+fn convert_doms_to_string(cfg : &Cfg, dom : HashMap<i32, HashSet<i32>>) -> HashMap<String, HashSet<String>> {
+    let mut dominators = HashMap::new();
+    for (v, doms) in dom {
+        let name = cfg.name_map.get_by_left(&v).unwrap().clone();
+        let name_doms = doms.into_iter().map(|d|
+            cfg.name_map.get_by_left(&d).unwrap().to_string()).collect();
+        dominators.insert(name, name_doms);
+    }
+    dominators
+}
+
+pub fn find_dominators(cfg : &Cfg) -> HashMap<String, HashSet<String>> {
     let dom = find_dominators_num(cfg);
-    let sdom = get_strict_doms(&dom);
+    convert_doms_to_string(cfg, dom)
+}
+
+/// Dominance frontiers computed from the immediate dominators: for each join
+/// point, walk up from every predecessor until reaching the join point's
+/// immediate dominator, adding the join point to each frontier on the way.
+pub fn get_dominance_frontier_num(cfg : &Cfg) -> HashMap<i32, HashSet<i32>> {
+    let idom = find_immediate_doms(cfg);
+    let reachable : HashSet<i32> = get_reverse_post_order(cfg).into_iter().collect();
     let mut frontier : HashMap<i32, HashSet<i32>> = HashMap::new();
-    for (a, _) in &dom {
-        let mut set_a = HashSet::new();
-        for (b, d) in &sdom {
-            if !d.contains(a) {
-                for p in cfg.pred.get(b).unwrap() {
-                    if dom.get(p).unwrap().contains(&a) {
-                        set_a.insert(*b);
-                    }
+    for v in cfg.block_map.keys() {
+        frontier.insert(*v, HashSet::new());
+    }
+    for b in &reachable {
+        let preds : Vec<&i32> = cfg.pred.get(b).unwrap().iter()
+            .filter(|p| reachable.contains(p)).collect();
+        if preds.len() < 2 {
+            continue;
+        }
+        let b_idom = *idom.get(b).unwrap();
+        for p in preds {
+            let mut runner = *p;
+            while Some(runner) != b_idom {
+                frontier.get_mut(&runner).unwrap().insert(*b);
+                match idom.get(&runner).unwrap() {
+                    Some(d) => runner = *d,
+                    None => break,
                 }
             }
         }
-        frontier.insert(*a, set_a);
     }
     frontier
 }
@@ -183,33 +303,161 @@ pub fn get_dominance_frontier(cfg : &Cfg) -> HashMap<String, HashSet<String>> {
     convert_doms_to_string(cfg, frontier)
 }
 
-pub fn print_dominance_frontier(frontier : &HashMap<String, HashSet<String>>) {
-    println!("Frontier");
-    println!("--------------------------------");
-    let mut frontier = frontier.into_iter().collect::<Vec<_>>();
-    frontier.sort_by_key(|(n, _)| *n);
-    for (node, front) in  frontier {
-        print!("{node}: ");
-        let mut front = front.into_iter().collect::<Vec<_>>();
-        front.sort();
-        println!("{front:?}");
+/// Number of the virtual exit node used by the post-dominator analyses. Every
+/// block without successors, such as a block ending in `ret`, flows into it.
+pub const VIRTUAL_EXIT : i32 = -1;
+
+/// The reverse of the CFG rooted at `VIRTUAL_EXIT`, as `(succ, pred)` maps.
+/// Blocks that cannot reach a return, such as the blocks of an infinite
+/// loop, are given an extra edge to the exit from the last of them in
+/// reverse post order so that every block is post-dominated by the exit.
+fn reverse_cfg(cfg : &Cfg) -> (HashMap<i32, Vec<i32>>, HashMap<i32, Vec<i32>>) {
+    let mut succ : HashMap<i32, Vec<i32>> = HashMap::new();
+    let mut pred : HashMap<i32, Vec<i32>> = HashMap::new();
+    succ.insert(VIRTUAL_EXIT, vec![]);
+    pred.insert(VIRTUAL_EXIT, vec![]);
+    for v in cfg.block_map.keys() {
+        succ.insert(*v, cfg.pred.get(v).unwrap().clone());
+        pred.insert(*v, cfg.succ.get(v).unwrap().clone());
+        if cfg.succ.get(v).unwrap().is_empty() {
+            succ.get_mut(&VIRTUAL_EXIT).unwrap().push(*v);
+            pred.get_mut(v).unwrap().push(VIRTUAL_EXIT);
+        }
+    }
+
+    let mut visited = HashSet::new();
+    post_order(&succ, VIRTUAL_EXIT, &mut visited);
+    let rpo = get_reverse_post_order(cfg);
+    let reachable : HashSet<i32> = rpo.iter().copied().collect();
+    let rest = cfg.block_map.keys().filter(|v| !reachable.contains(v)).copied();
+    let candidates : Vec<i32> = rpo.iter().rev().copied().chain(rest).collect();
+    for v in candidates {
+        if !visited.contains(&v) {
+            succ.get_mut(&VIRTUAL_EXIT).unwrap().push(v);
+            pred.get_mut(&v).unwrap().push(VIRTUAL_EXIT);
+            post_order(&succ, v, &mut visited);
+        }
+    }
+    (succ, pred)
+}
+
+/// Immediate post-dominator of every block, which is `VIRTUAL_EXIT` for the
+/// blocks that leave the function. The exit itself maps to `None`.
+pub fn find_immediate_post_doms(cfg : &Cfg) -> HashMap<i32, Option<i32>> {
+    let (succ, pred) = reverse_cfg(cfg);
+    let rpo : Vec<i32> = post_order(&succ, VIRTUAL_EXIT, &mut HashSet::new())
+        .into_iter().rev().collect();
+    immediate_doms(&rpo, &pred)
+}
+
+/// Post-dominator tree rooted at `VIRTUAL_EXIT`.
+pub fn form_post_dom_tree(cfg : &Cfg) -> DomTree {
+    let ipdom = find_immediate_post_doms(cfg);
+    dom_tree_from_idoms(cfg, &ipdom)
+}
+
+/// Post-dominators of every block: the block itself and its ancestors in the
+/// post-dominator tree, leaving out the virtual exit.
+pub fn find_post_dominators_num(cfg : &Cfg) -> HashMap<i32, HashSet<i32>> {
+    let ipdom = find_immediate_post_doms(cfg);
+    let mut pdom = HashMap::new();
+    for v in cfg.block_map.keys() {
+        let mut pdoms = HashSet::new();
+        let mut node = Some(*v);
+        while let Some(n) = node {
+            if n != VIRTUAL_EXIT {
+                pdoms.insert(n);
+            }
+            node = *ipdom.get(&n).unwrap();
+        }
+        pdom.insert(*v, pdoms);
     }
+    pdom
+}
+
+pub fn find_post_dominators(cfg : &Cfg) -> HashMap<String, HashSet<String>> {
+    let pdom = find_post_dominators_num(cfg);
+    convert_doms_to_string(cfg, pdom)
+}
+
+/// Names of related blocks for every block, such as its dominators or its
+/// dominance frontier, with the blocks and each set sorted by name. `title`
+/// heads the text printed by `Display` and is not serialized.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BlockSets {
+    #[serde(skip)]
+    pub title : &'static str,
+    #[serde(flatten)]
+    pub sets : IndexMap<String, Vec<String>>,
 }
 
-pub fn print_dominator_tree(tree : &DomTree) {
-    println!("Tree");
-    println!("--------------------------------");
-    for node in &tree.arena {
-        println!("{node:?}")
+impl BlockSets {
+    pub fn new(title : &'static str, map : &HashMap<String, HashSet<String>>) -> Self {
+        let mut names : Vec<&String> = map.keys().collect();
+        names.sort();
+        let sets = names.into_iter().map(|name| {
+            let mut set : Vec<String> = map[name].iter().cloned().collect();
+            set.sort();
+            (name.clone(), set)
+        }).collect();
+        BlockSets {title, sets}
     }
 }
 
-pub fn print_dominators(doms : &HashMap<String, HashSet<String>>) {
-    let mut doms : Vec<_> = doms.into_iter().collect();
-    doms.sort_by_key(|d| d.0);
-    println!("Dominators");
-    println!("--------------------------------");
-    for (name, dom) in doms {
-        println!("{name}: {dom:?}")
+impl fmt::Display for BlockSets {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}\n--------------------------------", self.title)?;
+        for (name, set) in &self.sets {
+            writeln!(f, "{name}: {set:?}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DomTree {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Tree\n--------------------------------")?;
+        for node in &self.arena {
+            writeln!(f, "{node:?}")?;
+        }
+        Ok(())
+    }
+}
+
+fn get_paths_to_node(cfg : &Cfg, entry_node : i32, node : i32, path : &[i32]) -> Vec<Vec<i32>> {
+    let mut path = path.to_vec();
+    path.push(entry_node);
+    if entry_node == node {
+        vec![path]
+    } else {
+        let mut paths = vec![];
+        for succ in cfg.succ.get(&entry_node).unwrap() {
+            if !path.contains(succ) {
+                let new_path = get_paths_to_node(cfg, *succ, node, &path);
+                paths.extend(new_path)
+            }
+        }
+        paths
+    }
+}
+
+fn dominates(cfg : &Cfg, entry_node : i32, node_a : i32, node_b : i32) -> bool {
+    let paths = get_paths_to_node(cfg, entry_node, node_b, &[]);
+    for path in paths {
+        if !path.contains(&node_a) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Checks `dom` by brute force: every dominator of a node must be on every
+/// path from the entry to that node. Panics otherwise.
+pub fn validate_dominators(cfg : &Cfg, dom : HashMap<i32, HashSet<i32>>) {
+    let (entry_node, _) = cfg.block_map.first().unwrap();
+    for (node, doms) in dom {
+        for d in doms {
+            assert!(dominates(cfg, *entry_node, d, node))
+        }
     }
 }
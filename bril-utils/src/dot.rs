@@ -0,0 +1,106 @@
+use crate::cfg::Cfg;
+use crate::df::DfReport;
+use crate::dominators::DomTree;
+use crate::loops::LoopForest;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// Extra information drawn on top of the CFG by `cfg_to_dot`. Every overlay
+/// is off by default.
+#[derive(Default, Clone, Copy)]
+pub struct Overlays<'a> {
+    /// Drawn as dashed blue edges from each block's immediate dominator.
+    pub dom_tree : Option<&'a DomTree>,
+    /// Drawn as dotted red edges from each block to its frontier, keyed by
+    /// block number as returned by `get_dominance_frontier_num`.
+    pub frontier : Option<&'a HashMap<i32, HashSet<i32>>>,
+    /// Every loop becomes a dashed cluster around its body, nested like the
+    /// loops themselves.
+    pub loops : Option<&'a LoopForest>,
+    /// Each block's in and out sets are added to the bottom of its node.
+    pub facts : Option<&'a DfReport>,
+}
+
+/// Escapes `s` for a double-quoted DOT string.
+fn escape(s : &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn node(cfg : &Cfg, num : i32, facts : Option<&DfReport>) -> String {
+    let name = cfg.name(num);
+    let mut label = format!("{}:\\l", escape(name));
+    for code in &cfg.block_map[&num].instrs {
+        write!(label, "  {}\\l", escape(code.to_string().trim())).unwrap();
+    }
+    if let Some(facts) = facts.and_then(|r| r.blocks.get(name)) {
+        write!(label, "in: {}\\lout: {}\\l", escape(&facts.ins.join(", ")), escape(&facts.outs.join(", "))).unwrap();
+    }
+    format!("b{num} [label=\"{label}\"];")
+}
+
+fn loop_cluster(out : &mut String, cfg : &Cfg, forest : &LoopForest, idx : usize,
+                nodes : &HashMap<i32, String>, indent : usize) {
+    let lp = forest.get_loop(idx);
+    let pad = "  ".repeat(indent);
+    writeln!(out, "{pad}subgraph cluster_loop{idx} {{").unwrap();
+    writeln!(out, "{pad}  label=\"loop {} (depth {})\";", escape(cfg.name(lp.header)), lp.depth).unwrap();
+    writeln!(out, "{pad}  style=dashed;").unwrap();
+    for child in &lp.children {
+        loop_cluster(out, cfg, forest, *child, nodes, indent + 1);
+    }
+    for num in cfg.block_map.keys() {
+        if forest.loop_of(*num).is_some_and(|l| l.idx == idx) {
+            writeln!(out, "{pad}  {}", nodes[num]).unwrap();
+        }
+    }
+    writeln!(out, "{pad}}}").unwrap();
+}
+
+/// Renders `cfg` as a Graphviz digraph named `name`, with one box per block
+/// listing its instructions and a solid edge per control-flow edge.
+pub fn cfg_to_dot(name : &str, cfg : &Cfg, overlays : &Overlays) -> String {
+    let nodes : HashMap<i32, String> = cfg.block_map.keys()
+        .map(|num| (*num, node(cfg, *num, overlays.facts))).collect();
+    let mut out = format!("digraph \"{}\" {{\n", escape(name));
+    out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+    match overlays.loops {
+        Some(forest) => {
+            for root in &forest.roots {
+                loop_cluster(&mut out, cfg, forest, *root, &nodes, 1);
+            }
+            for num in cfg.block_map.keys() {
+                if forest.loop_of(*num).is_none() {
+                    writeln!(out, "  {}", nodes[num]).unwrap();
+                }
+            }
+        },
+        None => {
+            for num in cfg.block_map.keys() {
+                writeln!(out, "  {}", nodes[num]).unwrap();
+            }
+        },
+    }
+    for num in cfg.block_map.keys() {
+        for succ in &cfg.succ[num] {
+            writeln!(out, "  b{num} -> b{succ};").unwrap();
+        }
+    }
+    if let Some(tree) = overlays.dom_tree {
+        for num in cfg.block_map.keys() {
+            if let Some(idom) = tree.idom(num) {
+                writeln!(out, "  b{idom} -> b{num} [style=dashed, color=blue, constraint=false];").unwrap();
+            }
+        }
+    }
+    if let Some(frontier) = overlays.frontier {
+        for num in cfg.block_map.keys() {
+            let mut front : Vec<i32> = frontier.get(num).into_iter().flatten().copied().collect();
+            front.sort();
+            for f in front {
+                writeln!(out, "  b{num} -> b{f} [style=dotted, color=red, constraint=false];").unwrap();
+            }
+        }
+    }
+    out.push_str("}\n");
+    out
+}
@@ -23,15 +23,27 @@ fn get_op(instr : &AbstractInstruction) -> String {
     }
 }
 
-fn is_terminator(instr : &AbstractInstruction) -> bool {
-    let op = get_op(instr);
-    if op == "br" || op == "jmp" || op == "ret" {
-        true
-    } else {
-        false
+pub fn get_dest(instr : &AbstractInstruction) -> Option<&String> {
+    match instr {
+        AbstractInstruction::Constant {dest, ..}
+        | AbstractInstruction::Value {dest, ..} => Some(dest),
+        AbstractInstruction::Effect {..} => None,
+    }
+}
+
+pub fn get_args(instr : &AbstractInstruction) -> &[String] {
+    match instr {
+        AbstractInstruction::Value {args, ..}
+        | AbstractInstruction::Effect {args, ..} => args,
+        AbstractInstruction::Constant {..} => &[],
     }
 }
 
+pub fn is_terminator(instr : &AbstractInstruction) -> bool {
+    let op = get_op(instr);
+    op == "br" || op == "jmp" || op == "ret"
+}
+
 fn get_code(func : &AbstractFunction) -> Vec<&AbstractCode> {
     let mut code = Vec::new();
     for instr in &func.instrs {
@@ -47,7 +59,7 @@ pub fn form_blocks(func : &AbstractFunction) -> Vec<Block>{
     for i in instrs {
         match i {
             AbstractCode::Instruction(instr) => {
-                let term = is_terminator(&instr);
+                let term = is_terminator(instr);
                 cur_block.instrs.push(i.clone());
                 if term {
                     blocks.push(cur_block);
@@ -92,9 +104,7 @@ pub fn print_func_blocks(func : &AbstractFunction) {
 pub fn flatten_blocks(blocks : Vec<Block>) -> Vec<AbstractCode> {
     let mut instrs = Vec::new();
     for block in blocks {
-        for instr in block.instrs {
-            instrs.push(instr.clone());
-        }
+        instrs.extend(block.instrs);
     }
     instrs
 }
@@ -0,0 +1,230 @@
+use std::collections::{HashMap, HashSet};
+use bril_rs::{AbstractFunction, AbstractCode, AbstractInstruction};
+use crate::cfg::*;
+use crate::dominators::form_dom_tree;
+use crate::form_blocks::{form_blocks, get_dest};
+use crate::lvn::{is_commutative, purity, Purity, ValueExpr};
+use crate::ssa::{from_ssa, to_ssa};
+
+/// Value numbering state shared by the whole function. Since every variable
+/// is assigned once, `var2num` never has to be undone; only the expression
+/// table is scoped to the dominator subtree that is being visited.
+#[derive(Default)]
+struct GvnTable {
+    leaders : Vec<String>,
+    var2num : HashMap<String, i32>,
+    exprs : HashMap<ValueExpr, i32>,
+}
+
+impl GvnTable {
+    fn fresh_value(&mut self, var : &str) -> i32 {
+        let num : i32 = self.leaders.len().try_into().unwrap();
+        self.leaders.push(var.to_string());
+        self.var2num.insert(var.to_string(), num);
+        num
+    }
+
+    fn leader(&self, var : &String) -> Option<&String> {
+        self.var2num.get(var).map(|num| &self.leaders[*num as usize])
+    }
+
+    fn rename(&self, args : &mut [String]) {
+        for a in args.iter_mut() {
+            if let Some(leader) = self.leader(a) {
+                *a = leader.to_string();
+            }
+        }
+    }
+
+    /// Numbers `dest` as `value_expr`. Returns the leader of the value when it
+    /// was already computed in a dominating block. New expressions are
+    /// recorded in `scope` so they can be forgotten on leaving the subtree.
+    fn number(&mut self, value_expr : ValueExpr, dest : &str, scope : &mut Vec<ValueExpr>) -> Option<String> {
+        match self.exprs.get(&value_expr) {
+            Some(num) => {
+                self.var2num.insert(dest.to_string(), *num);
+                Some(self.leaders[*num as usize].to_string())
+            },
+            None => {
+                let num = self.fresh_value(dest);
+                self.exprs.insert(value_expr.clone(), num);
+                scope.push(value_expr);
+                None
+            },
+        }
+    }
+
+    fn arg_nums(&self, args : &[String]) -> Option<Vec<i32>> {
+        args.iter().map(|a| self.var2num.get(a).copied()).collect()
+    }
+}
+
+/// Whether every variable, arguments included, is assigned at most once, so
+/// that a name stands for the same value in every block its definition
+/// dominates.
+fn is_ssa(func : &AbstractFunction) -> bool {
+    let mut defined : HashSet<&String> = func.args.iter().map(|a| &a.name).collect();
+    func.instrs.iter().all(|code| match code {
+        AbstractCode::Instruction(instr) => match get_dest(instr) {
+            Some(dest) => defined.insert(dest),
+            None => true,
+        },
+        AbstractCode::Label {..} => true,
+    })
+}
+
+fn id_instr(instr : &AbstractInstruction, leader : &str) -> AbstractInstruction {
+    match instr {
+        AbstractInstruction::Value {dest, op_type, ..} => AbstractInstruction::Value {
+            op : "id".to_string(),
+            args : vec![leader.to_string()],
+            dest : dest.to_string(),
+            op_type : op_type.clone(),
+            funcs : vec![],
+            labels : vec![],
+        },
+        _ => instr.clone(),
+    }
+}
+
+fn phi_expr(table : &GvnTable, args : &[String], labels : &[String]) -> Option<ValueExpr> {
+    let mut incoming : Vec<(&String, &String)> = labels.iter().zip(args).collect();
+    incoming.sort();
+    let (labels, args) : (Vec<&String>, Vec<String>) = incoming.into_iter()
+        .map(|(l, a)| (l, a.to_string())).unzip();
+    let nums = table.arg_nums(&args)?;
+    let op_code = labels.into_iter().fold("phi".to_string(), |acc, l| acc + " " + l);
+    Some(ValueExpr::new(op_code, nums))
+}
+
+enum Visit {
+    Enter(i32),
+    Exit(Vec<ValueExpr>),
+}
+
+fn gvn_block(cfg : &mut Cfg, num : i32, table : &mut GvnTable, prop : bool, comm : bool) -> Vec<ValueExpr> {
+    let mut scope = vec![];
+    let block = cfg.block_map.get_mut(&num).unwrap();
+    for code in block.instrs.iter_mut() {
+        let instr = match code {
+            AbstractCode::Instruction(instr) => instr,
+            AbstractCode::Label {..} => continue,
+        };
+        match instr {
+            AbstractInstruction::Constant {dest, value, ..} => {
+                let value_expr = ValueExpr::new(format!("const {value:?}"), vec![]);
+                table.number(value_expr, dest, &mut scope);
+            },
+            AbstractInstruction::Value {op, args, dest, labels, ..} if op == "phi" => {
+                let nums = table.arg_nums(args);
+                match nums {
+                    // A phi choosing between copies of one value is that value.
+                    Some(nums) if !nums.is_empty() && nums.iter().all(|n| *n == nums[0]) => {
+                        table.var2num.insert(dest.to_string(), nums[0]);
+                        let leader = table.leaders[nums[0] as usize].to_string();
+                        *instr = id_instr(instr, &leader);
+                    },
+                    _ => match phi_expr(table, args, labels) {
+                        Some(value_expr) => {
+                            if let Some(leader) = table.number(value_expr, dest, &mut scope) {
+                                *instr = id_instr(instr, &leader);
+                            }
+                        },
+                        None => {
+                            table.fresh_value(dest);
+                        },
+                    },
+                }
+            },
+            AbstractInstruction::Value {op, args, dest, ..} => {
+                table.rename(args);
+                let nums = if purity(op) == Purity::Pure { table.arg_nums(args) } else { None };
+                match nums {
+                    Some(nums) if prop && op == "id" => {
+                        table.var2num.insert(dest.to_string(), nums[0]);
+                    },
+                    Some(mut nums) => {
+                        if comm && is_commutative(op) {
+                            nums.sort();
+                        }
+                        let value_expr = ValueExpr::new(op.to_string(), nums);
+                        if let Some(leader) = table.number(value_expr, dest, &mut scope) {
+                            *instr = id_instr(instr, &leader);
+                        }
+                    },
+                    None => {
+                        table.fresh_value(dest);
+                    },
+                }
+            },
+            AbstractInstruction::Effect {args, ..} => table.rename(args),
+        }
+    }
+
+    let name = cfg.name_map.get_by_left(&num).unwrap().to_string();
+    for s in cfg.succ.get(&num).unwrap().clone() {
+        for code in cfg.block_map.get_mut(&s).unwrap().instrs.iter_mut() {
+            if let AbstractCode::Instruction(AbstractInstruction::Value {op, args, labels, ..}) = code {
+                if op == "phi" {
+                    for (a, l) in args.iter_mut().zip(labels.iter()) {
+                        if *l == name {
+                            if let Some(leader) = table.leader(a) {
+                                *a = leader.to_string();
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    scope
+}
+
+/// Dominator-based global value numbering for a function in SSA form, such as
+/// the output of `ssa`. Expressions are numbered in a preorder walk of the
+/// dominator tree, so an expression computed in a block is reused by every
+/// block it dominates. `prop` and `comm` enable copy propagation and
+/// commutativity as in `local_value_numbering`. A function that is not in
+/// SSA form is converted into it first and back out afterwards, unless
+/// numbering finds nothing to replace, in which case it is left as it was.
+pub fn global_value_numbering(func : &mut AbstractFunction, prop : bool, comm : bool) {
+    let original = (!is_ssa(func)).then(|| func.clone());
+    if original.is_some() {
+        to_ssa(func);
+    }
+    let in_ssa = func.instrs.clone();
+    let mut cfg = form_cfg(form_blocks(func));
+    let dom_tree = form_dom_tree(&cfg);
+    let mut table = GvnTable::default();
+    for a in &func.args {
+        table.fresh_value(&a.name);
+    }
+
+    let (&entry, _) = cfg.block_map.first().unwrap();
+    let mut stack = vec![Visit::Enter(entry)];
+    while let Some(visit) = stack.pop() {
+        match visit {
+            Visit::Enter(num) => {
+                let scope = gvn_block(&mut cfg, num, &mut table, prop, comm);
+                stack.push(Visit::Exit(scope));
+                for child in dom_tree.children(&num).into_iter().rev() {
+                    stack.push(Visit::Enter(child));
+                }
+            },
+            Visit::Exit(scope) => {
+                for value_expr in scope {
+                    table.exprs.remove(&value_expr);
+                }
+            },
+        }
+    }
+
+    func.instrs = reassemble(cfg);
+    if let Some(original) = original {
+        if func.instrs == in_ssa {
+            *func = original;
+        } else {
+            from_ssa(func);
+        }
+    }
+}
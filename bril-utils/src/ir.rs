@@ -0,0 +1,414 @@
+use std::collections::{HashMap, HashSet};
+use bril_rs::{AbstractArgument, AbstractCode, AbstractFunction, AbstractInstruction, AbstractType, ConstOps, Literal};
+
+macro_rules! id {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(u32);
+
+        impl $name {
+            pub fn new(index : usize) -> Self {
+                $name(index as u32)
+            }
+
+            pub fn index(self) -> usize {
+                self.0 as usize
+            }
+        }
+    };
+}
+
+id!(
+    /// A block in the arena of a `Function`. Ids stay valid when blocks are
+    /// removed from the layout.
+    BlockId
+);
+id!(
+    /// An instruction in the arena of a `Function`. Removing an instruction
+    /// from its block leaves it in the arena.
+    InstId
+);
+id!(
+    /// An interned variable name.
+    VarId
+);
+
+/// Maps variable names to dense `VarId`s and back.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    names : Vec<String>,
+    ids : HashMap<String, VarId>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, name : &str) -> VarId {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = VarId::new(self.names.len());
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        id
+    }
+
+    pub fn get(&self, name : &str) -> Option<VarId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id : VarId) -> &str {
+        &self.names[id.index()]
+    }
+
+    /// Interns a name based on `seed` that is not in use yet.
+    pub fn fresh(&mut self, seed : &str) -> VarId {
+        let mut i = 1;
+        while self.ids.contains_key(&format!("{seed}{i}")) {
+            i += 1;
+        }
+        self.intern(&format!("{seed}{i}"))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// One instruction. A `const` has a `value`; an instruction with a `dest` is
+/// a value operation and anything else is an effect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inst {
+    pub op : String,
+    pub dest : Option<VarId>,
+    pub ty : Option<AbstractType>,
+    pub args : Vec<VarId>,
+    pub funcs : Vec<String>,
+    pub labels : Vec<BlockId>,
+    pub value : Option<Literal>,
+}
+
+impl Inst {
+    pub fn is_terminator(&self) -> bool {
+        matches!(self.op.as_str(), "jmp" | "br" | "ret")
+    }
+}
+
+/// A basic block. Blocks that had no label in the input are given a fresh
+/// `name` but are only printed with a label if something jumps to them.
+#[derive(Debug, Clone, Default)]
+pub struct BlockData {
+    pub name : String,
+    pub labelled : bool,
+    pub insts : Vec<InstId>,
+    pub succ : Vec<BlockId>,
+    pub pred : Vec<BlockId>,
+}
+
+/// A function with its blocks and instructions in arenas. `layout` is the
+/// order of the blocks in the function; blocks that are not in it, such as
+/// the targets of labels that do not exist, are never printed.
+#[derive(Debug, Clone, Default)]
+pub struct Function {
+    pub name : String,
+    pub args : Vec<(VarId, AbstractType)>,
+    pub return_type : Option<AbstractType>,
+    pub vars : Interner,
+    pub layout : Vec<BlockId>,
+    insts : Vec<Inst>,
+    blocks : Vec<BlockData>,
+}
+
+impl Function {
+    pub fn from_abstract(func : &AbstractFunction) -> Self {
+        let mut f = Function {
+            name : func.name.clone(),
+            return_type : func.return_type.clone(),
+            ..Default::default()
+        };
+        for a in &func.args {
+            let var = f.vars.intern(&a.name);
+            f.args.push((var, a.arg_type.clone()));
+        }
+
+        let mut labels : HashMap<&str, BlockId> = HashMap::new();
+        let used_names : HashSet<&str> = func.instrs.iter().filter_map(|code| match code {
+            AbstractCode::Label {label} => Some(label.as_str()),
+            _ => None,
+        }).collect();
+        let mut next_name = 0;
+
+        let mut placed : HashSet<BlockId> = HashSet::new();
+        let mut current : Option<BlockId> = None;
+        for code in &func.instrs {
+            match code {
+                AbstractCode::Label {label} => {
+                    // A jump may have created the block already; a duplicate
+                    // label gets a block of its own that nothing jumps to.
+                    let block = match labels.get(label.as_str()) {
+                        Some(b) if !placed.contains(b) => *b,
+                        _ => f.add_block(label.clone(), true),
+                    };
+                    labels.entry(label).or_insert(block);
+                    placed.insert(block);
+                    f.layout.push(block);
+                    current = Some(block);
+                },
+                AbstractCode::Instruction(instr) => {
+                    let block = match current {
+                        Some(b) => b,
+                        None => {
+                            let name = loop {
+                                next_name += 1;
+                                let name = format!("b{next_name}");
+                                if !used_names.contains(name.as_str()) {
+                                    break name;
+                                }
+                            };
+                            let b = f.add_block(name, false);
+                            placed.insert(b);
+                            f.layout.push(b);
+                            b
+                        },
+                    };
+                    let inst = f.convert_instr(instr, &mut labels);
+                    let is_term = inst.is_terminator();
+                    let id = f.add_inst(inst);
+                    f.blocks[block.index()].insts.push(id);
+                    current = if is_term { None } else { Some(block) };
+                },
+            }
+        }
+        f.compute_edges();
+        f
+    }
+
+    fn convert_instr<'a>(&mut self, instr : &'a AbstractInstruction, labels : &mut HashMap<&'a str, BlockId>) -> Inst {
+        match instr {
+            AbstractInstruction::Constant {dest, const_type, value, ..} => Inst {
+                op : "const".to_string(),
+                dest : Some(self.vars.intern(dest)),
+                ty : const_type.clone(),
+                args : vec![],
+                funcs : vec![],
+                labels : vec![],
+                value : Some(value.clone()),
+            },
+            AbstractInstruction::Value {args, dest, funcs, labels : targets, op, op_type} => Inst {
+                op : op.clone(),
+                dest : Some(self.vars.intern(dest)),
+                ty : op_type.clone(),
+                args : args.iter().map(|a| self.vars.intern(a)).collect(),
+                funcs : funcs.clone(),
+                labels : targets.iter().map(|l| self.resolve(l, labels)).collect(),
+                value : None,
+            },
+            AbstractInstruction::Effect {args, funcs, labels : targets, op} => Inst {
+                op : op.clone(),
+                dest : None,
+                ty : None,
+                args : args.iter().map(|a| self.vars.intern(a)).collect(),
+                funcs : funcs.clone(),
+                labels : targets.iter().map(|l| self.resolve(l, labels)).collect(),
+                value : None,
+            },
+        }
+    }
+
+    /// The block of `label`. A label that has not been seen yet gets a new
+    /// block, which is placed in the layout when the label turns up and stays
+    /// outside it if the label does not exist.
+    fn resolve<'a>(&mut self, label : &'a str, labels : &mut HashMap<&'a str, BlockId>) -> BlockId {
+        if let Some(b) = labels.get(label) {
+            return *b;
+        }
+        let b = self.add_block(label.to_string(), true);
+        labels.insert(label, b);
+        b
+    }
+
+    pub fn add_block(&mut self, name : String, labelled : bool) -> BlockId {
+        let id = BlockId::new(self.blocks.len());
+        self.blocks.push(BlockData {name, labelled, ..Default::default()});
+        id
+    }
+
+    /// Adds `inst` to the arena. It is not part of any block until its id is
+    /// pushed onto one.
+    pub fn add_inst(&mut self, inst : Inst) -> InstId {
+        let id = InstId::new(self.insts.len());
+        self.insts.push(inst);
+        id
+    }
+
+    pub fn block(&self, id : BlockId) -> &BlockData {
+        &self.blocks[id.index()]
+    }
+
+    pub fn block_mut(&mut self, id : BlockId) -> &mut BlockData {
+        &mut self.blocks[id.index()]
+    }
+
+    pub fn inst(&self, id : InstId) -> &Inst {
+        &self.insts[id.index()]
+    }
+
+    pub fn inst_mut(&mut self, id : InstId) -> &mut Inst {
+        &mut self.insts[id.index()]
+    }
+
+    /// The number of blocks in the arena, including ones that are not in the
+    /// layout. Useful to size tables indexed by `BlockId`.
+    pub fn num_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn num_insts(&self) -> usize {
+        self.insts.len()
+    }
+
+    /// The instructions of the blocks in the layout, in order.
+    pub fn insts(&self) -> impl Iterator<Item=(BlockId, InstId)> + '_ {
+        self.layout.iter().flat_map(move |b| self.blocks[b.index()].insts.iter().map(move |i| (*b, *i)))
+    }
+
+    /// Keeps the instructions of every block for which `keep` returns true.
+    /// Returns whether any were removed.
+    pub fn retain_insts(&mut self, mut keep : impl FnMut(&Inst) -> bool) -> bool {
+        let mut removed = false;
+        for b in &self.layout {
+            let insts = &self.insts;
+            let block = &mut self.blocks[b.index()];
+            let before = block.insts.len();
+            block.insts.retain(|i| keep(&insts[i.index()]));
+            removed |= block.insts.len() != before;
+        }
+        removed
+    }
+
+    /// The block after the one at `pos` in the layout, if control falls
+    /// through to it.
+    fn fallthrough(&self, pos : usize) -> Option<BlockId> {
+        let block = &self.blocks[self.layout[pos].index()];
+        match block.insts.last() {
+            Some(i) if self.insts[i.index()].is_terminator() => None,
+            _ => self.layout.get(pos + 1).copied(),
+        }
+    }
+
+    /// Recomputes `succ` and `pred` of every block from the terminators and
+    /// the layout.
+    pub fn compute_edges(&mut self) {
+        for block in &mut self.blocks {
+            block.succ.clear();
+            block.pred.clear();
+        }
+        for pos in 0..self.layout.len() {
+            let b = self.layout[pos];
+            let succ = match self.fallthrough(pos) {
+                Some(next) => vec![next],
+                None => match self.blocks[b.index()].insts.last() {
+                    Some(i) => self.insts[i.index()].labels.clone(),
+                    None => vec![],
+                },
+            };
+            for s in &succ {
+                self.blocks[s.index()].pred.push(b);
+            }
+            self.blocks[b.index()].succ = succ;
+        }
+    }
+
+    /// Converts one instruction back to Bril.
+    pub fn instr_to_abstract(&self, inst : &Inst) -> AbstractInstruction {
+        let args = inst.args.iter().map(|a| self.vars.name(*a).to_string()).collect();
+        let labels = inst.labels.iter().map(|l| self.blocks[l.index()].name.clone()).collect();
+        match (&inst.value, inst.dest) {
+            (Some(value), Some(dest)) => AbstractInstruction::Constant {
+                dest : self.vars.name(dest).to_string(),
+                op : ConstOps::Const,
+                const_type : inst.ty.clone(),
+                value : value.clone(),
+            },
+            (_, Some(dest)) => AbstractInstruction::Value {
+                args,
+                dest : self.vars.name(dest).to_string(),
+                funcs : inst.funcs.clone(),
+                labels,
+                op : inst.op.clone(),
+                op_type : inst.ty.clone(),
+            },
+            (_, None) => AbstractInstruction::Effect {
+                args,
+                funcs : inst.funcs.clone(),
+                labels,
+                op : inst.op.clone(),
+            },
+        }
+    }
+
+    /// Converts back to a Bril function. Blocks are printed in layout order,
+    /// and a `jmp` is added where a block used to fall through to a block that
+    /// no longer follows it.
+    pub fn to_abstract(&self) -> AbstractFunction {
+        let mut jumps : Vec<Option<BlockId>> = vec![None; self.layout.len()];
+        let mut targeted = vec![false; self.blocks.len()];
+        for (pos, b) in self.layout.iter().enumerate() {
+            let block = &self.blocks[b.index()];
+            let ends_in_terminator = block.insts.last().is_some_and(|i| self.insts[i.index()].is_terminator());
+            if let (false, Some(s)) = (ends_in_terminator, block.succ.first()) {
+                if self.layout.get(pos + 1) != Some(s) {
+                    jumps[pos] = Some(*s);
+                    targeted[s.index()] = true;
+                }
+            }
+        }
+        for (_, i) in self.insts() {
+            for l in &self.insts[i.index()].labels {
+                targeted[l.index()] = true;
+            }
+        }
+
+        let mut instrs = Vec::with_capacity(self.insts.len() + self.layout.len());
+        for (pos, b) in self.layout.iter().enumerate() {
+            let block = &self.blocks[b.index()];
+            if block.labelled || targeted[b.index()] {
+                instrs.push(AbstractCode::Label {label : block.name.clone()});
+            }
+            for i in &block.insts {
+                instrs.push(AbstractCode::Instruction(self.instr_to_abstract(&self.insts[i.index()])));
+            }
+            if let Some(target) = jumps[pos] {
+                instrs.push(AbstractCode::Instruction(AbstractInstruction::Effect {
+                    args : vec![],
+                    funcs : vec![],
+                    labels : vec![self.blocks[target.index()].name.clone()],
+                    op : "jmp".to_string(),
+                }));
+            }
+        }
+        AbstractFunction {
+            name : self.name.clone(),
+            args : self.args.iter().map(|(v, t)| AbstractArgument {
+                name : self.vars.name(*v).to_string(),
+                arg_type : t.clone(),
+            }).collect(),
+            return_type : self.return_type.clone(),
+            instrs,
+        }
+    }
+}
+
+/// Runs `pass` on the arena form of `func` and writes the result back if the
+/// pass reports a change.
+pub fn with_ir(func : &mut AbstractFunction, pass : impl FnOnce(&mut Function) -> bool) -> bool {
+    let mut ir = Function::from_abstract(func);
+    let changed = pass(&mut ir);
+    if changed {
+        *func = ir.to_abstract();
+    }
+    changed
+}
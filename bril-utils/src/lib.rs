@@ -1,5 +1,23 @@
 pub mod cfg;
 pub mod df;
+pub mod chains;
+pub mod const_prop;
 pub mod form_blocks;
 pub mod dominators;
+pub mod control_dep;
+pub mod loops;
+pub mod dot;
+pub mod licm;
+pub mod adce;
+pub mod pre;
+pub mod simplify_cfg;
 pub mod tdce;
+pub mod bitset;
+pub mod liveness;
+pub mod ir;
+pub mod lvn;
+pub mod gvn;
+pub mod ssa;
+pub mod sccp;
+pub mod verify;
+pub mod pass;
@@ -0,0 +1,223 @@
+use bril_rs::{AbstractFunction, AbstractCode, AbstractInstruction, Literal};
+
+use crate::cfg::{Cfg, form_cfg, fresh, reassemble};
+use crate::chains::{def_use_chains, DefUseChains, Use};
+use crate::df::{df_analysis, Def, LiveVars, ProgramPoint};
+use crate::dominators::{DomTree, form_dom_tree};
+use crate::form_blocks::{Block, form_blocks, get_args};
+use crate::loops::{find_loops, Loop};
+use std::collections::{HashMap, HashSet};
+
+fn get_instr(cfg : &Cfg, point : ProgramPoint) -> Option<&AbstractInstruction> {
+    match cfg.block_map.get(&point.block).unwrap().instrs.get(point.index) {
+        Some(AbstractCode::Instruction(instr)) => Some(instr),
+        _ => None,
+    }
+}
+
+fn has_phis(block : &Block) -> bool {
+    block.instrs.iter().any(|code| matches!(code,
+        AbstractCode::Instruction(AbstractInstruction::Value {op, ..}) if op == "phi"))
+}
+
+/// Returns the preheader of `l`, inserting an empty block in front of the
+/// header when there is no single outside predecessor that jumps only to
+/// the header. Returns `None` if the header has phis that cannot be
+/// redirected to a new block.
+fn get_preheader(cfg : &mut Cfg, l : &Loop) -> Option<i32> {
+    let header = l.header;
+    let outside : Vec<i32> = cfg.pred.get(&header).unwrap().iter().copied()
+        .filter(|p| !l.contains(*p)).collect();
+    if outside.len() == 1 && cfg.succ.get(&outside[0]).unwrap().len() == 1 {
+        return Some(outside[0]);
+    }
+    if outside.len() > 1 && has_phis(cfg.block_map.get(&header).unwrap()) {
+        return None;
+    }
+
+    let header_name = cfg.name_map.get_by_left(&header).unwrap().to_string();
+    let name = fresh(format!("{header_name}.preheader"), cfg.name_map.right_values());
+    let jmp = AbstractCode::Instruction(AbstractInstruction::Effect {
+        op : "jmp".to_string(),
+        args : vec![],
+        funcs : vec![],
+        labels : vec![header_name.to_string()],
+    });
+    let pre = cfg.insert_block(name, Block {instrs : vec![jmp]}, Some(header));
+    for p in outside {
+        cfg.redirect_edge(p, header, pre);
+    }
+    Some(pre)
+}
+
+/// Value operations that can be executed speculatively. `div` is only safe
+/// with a divisor that is a non-zero constant.
+fn is_hoistable_op(op : &str) -> bool {
+    matches!(op, "id" | "add" | "mul" | "sub" | "eq" | "lt" | "gt" | "le" | "ge"
+        | "not" | "and" | "or" | "fadd" | "fmul" | "fsub" | "fdiv"
+        | "feq" | "flt" | "fgt" | "fle" | "fge" | "ptradd")
+}
+
+fn is_nonzero_const(cfg : &Cfg, def : &Def) -> bool {
+    match def.point.and_then(|p| get_instr(cfg, p)) {
+        Some(AbstractInstruction::Constant {value : Literal::Int(v), ..}) => *v != 0,
+        _ => false,
+    }
+}
+
+struct LoopInfo<'a> {
+    cfg : &'a Cfg,
+    l : &'a Loop,
+    chains : DefUseChains,
+    dom_tree : DomTree,
+    live_in : HashMap<i32, HashSet<String>>,
+    hoisted : Vec<ProgramPoint>,
+}
+
+impl<'a> LoopInfo<'a> {
+    fn inside(&self, def : &Def) -> bool {
+        match def.point {
+            Some(p) => self.l.contains(p.block),
+            None => false,
+        }
+    }
+
+    /// Every argument is defined outside the loop or by a single definition
+    /// that has already been hoisted.
+    fn args_invariant(&self, point : ProgramPoint, instr : &AbstractInstruction) -> bool {
+        get_args(instr).iter().all(|a| {
+            let defs = self.chains.defs(&Use::new(a.to_string(), point));
+            if defs.iter().all(|d| !self.inside(d)) {
+                return true;
+            }
+            defs.len() == 1 && self.hoisted.contains(&defs[0].point.unwrap())
+        })
+    }
+
+    /// The definition is the only one of its variable in the loop, reaches
+    /// every use of that variable in the loop on its own, and does not
+    /// change the value seen after any exit it does not dominate.
+    fn dest_movable(&self, point : ProgramPoint, dest : &String) -> bool {
+        let def = Def::new(dest.to_string(), Some(point));
+        let others = self.l.body.iter().any(|b| {
+            self.cfg.block_map.get(b).unwrap().instrs.iter().enumerate().any(|(i, code)| {
+                let p = ProgramPoint::new(*b, i);
+                p != point && matches!(code, AbstractCode::Instruction(
+                    AbstractInstruction::Value {dest : d, ..}
+                    | AbstractInstruction::Constant {dest : d, ..}) if d == dest)
+            })
+        });
+        if others {
+            return false;
+        }
+        for (u, defs) in &self.chains.use_def {
+            if &u.var == dest && self.l.contains(u.point.block) && defs != &vec![def.clone()] {
+                return false;
+            }
+        }
+        self.l.exits.iter().all(|(from, to)| {
+            self.dom_tree.dominates(&point.block, from)
+                || !self.live_in.get(to).unwrap().contains(dest)
+        })
+    }
+
+    fn movable(&self, point : ProgramPoint) -> bool {
+        match get_instr(self.cfg, point) {
+            Some(instr @ AbstractInstruction::Constant {dest, ..}) =>
+                self.args_invariant(point, instr) && self.dest_movable(point, dest),
+            Some(instr @ AbstractInstruction::Value {op, dest, args, ..}) => {
+                let op_ok = if op == "div" {
+                    let defs = self.chains.defs(&Use::new(args[1].to_string(), point));
+                    defs.len() == 1 && is_nonzero_const(self.cfg, &defs[0])
+                } else {
+                    is_hoistable_op(op)
+                };
+                op_ok && self.args_invariant(point, instr) && self.dest_movable(point, dest)
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Finds the instructions of `l` that can be hoisted, in an order where every
+/// instruction comes after the hoisted definitions it reads.
+fn find_hoistable(cfg : &Cfg, l : &Loop, args : &[String]) -> Vec<ProgramPoint> {
+    let live = df_analysis(cfg, &LiveVars);
+    let mut info = LoopInfo {
+        cfg,
+        l,
+        chains : def_use_chains(cfg, args),
+        dom_tree : form_dom_tree(cfg),
+        live_in : live.in_map,
+        hoisted : vec![],
+    };
+
+    let mut blocks : Vec<i32> = l.body.iter().copied().collect();
+    blocks.sort();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for b in &blocks {
+            for i in 0..cfg.block_map.get(b).unwrap().len() {
+                let point = ProgramPoint::new(*b, i);
+                if !info.hoisted.contains(&point) && info.movable(point) {
+                    info.hoisted.push(point);
+                    changed = true;
+                }
+            }
+        }
+    }
+    info.hoisted
+}
+
+fn hoist(cfg : &mut Cfg, pre : i32, points : Vec<ProgramPoint>) {
+    let mut moved = vec![];
+    for p in &points {
+        let block = cfg.block_map.get(&p.block).unwrap();
+        moved.push(block.instrs[p.index].clone());
+    }
+    let mut sorted = points;
+    sorted.sort();
+    for p in sorted.into_iter().rev() {
+        cfg.block_map.get_mut(&p.block).unwrap().instrs.remove(p.index);
+    }
+    let pre_block = cfg.block_map.get_mut(&pre).unwrap();
+    let term = pre_block.instrs.pop().unwrap();
+    pre_block.instrs.extend(moved);
+    pre_block.instrs.push(term);
+}
+
+/// Loop-invariant code motion. Loops are processed from the innermost
+/// outwards, so code can move out of several levels of nesting. Returns
+/// whether any instruction moved.
+pub fn licm(func : &mut AbstractFunction) -> bool {
+    let mut cfg = form_cfg(form_blocks(func));
+    let args : Vec<String> = func.args.iter().map(|a| a.name.clone()).collect();
+    let mut done : HashSet<i32> = HashSet::new();
+    let mut changed = false;
+
+    loop {
+        let forest = find_loops(&cfg);
+        let next = forest.inner_to_outer().into_iter()
+            .map(|idx| forest.get_loop(idx))
+            .find(|l| !done.contains(&l.header));
+        let l = match next {
+            Some(l) => l.clone(),
+            None => break,
+        };
+        done.insert(l.header);
+
+        let pre = match get_preheader(&mut cfg, &l) {
+            Some(pre) => pre,
+            None => continue,
+        };
+        let points = find_hoistable(&cfg, &l, &args);
+        if !points.is_empty() {
+            hoist(&mut cfg, pre, points);
+            changed = true;
+        }
+    }
+
+    func.instrs = reassemble(cfg);
+    changed
+}
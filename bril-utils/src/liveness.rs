@@ -0,0 +1,256 @@
+use crate::bitset::BitSet;
+use crate::ir::{BlockId, Function, Inst, InstId, VarId};
+use std::collections::VecDeque;
+
+/// The variables live at every point of a function in the arena IR. A
+/// variable is live at a point if some path from there reads it before
+/// writing it. Sets hold `VarId` indices. The arguments of a `phi` count as
+/// ordinary uses, so convert out of SSA first for exact results.
+pub struct Liveness {
+    live_in : Vec<BitSet>,
+    live_out : Vec<BitSet>,
+    live_after : Vec<BitSet>,
+}
+
+/// Turns the set live after `inst` into the set live before it.
+fn step_back(live : &mut BitSet, inst : &Inst) {
+    if let Some(dest) = inst.dest {
+        live.remove(dest.index());
+    }
+    for a in &inst.args {
+        live.insert(a.index());
+    }
+}
+
+impl Liveness {
+    pub fn new(func : &Function) -> Self {
+        let (blocks, vars) = (func.num_blocks(), func.vars.len());
+        let mut uses = vec![BitSet::new(vars); blocks];
+        let mut defs = vec![BitSet::new(vars); blocks];
+        for b in &func.layout {
+            let (u, d) = (&mut uses[b.index()], &mut defs[b.index()]);
+            for i in &func.block(*b).insts {
+                let inst = func.inst(*i);
+                for a in &inst.args {
+                    if !d.contains(a.index()) {
+                        u.insert(a.index());
+                    }
+                }
+                if let Some(dest) = inst.dest {
+                    d.insert(dest.index());
+                }
+            }
+        }
+
+        // Starting from the end of the layout lets most blocks see their
+        // successors before they are visited.
+        let mut live_in = vec![BitSet::new(vars); blocks];
+        let mut live_out = vec![BitSet::new(vars); blocks];
+        let mut queue : VecDeque<BlockId> = func.layout.iter().rev().copied().collect();
+        let mut queued = vec![false; blocks];
+        for b in &queue {
+            queued[b.index()] = true;
+        }
+        while let Some(b) = queue.pop_front() {
+            queued[b.index()] = false;
+            let mut out = BitSet::new(vars);
+            for s in &func.block(b).succ {
+                out.union_with(&live_in[s.index()]);
+            }
+            let mut live = out.clone();
+            live.difference_with(&defs[b.index()]);
+            live.union_with(&uses[b.index()]);
+            live_out[b.index()] = out;
+            if live != live_in[b.index()] {
+                live_in[b.index()] = live;
+                for p in &func.block(b).pred {
+                    if !queued[p.index()] {
+                        queued[p.index()] = true;
+                        queue.push_back(*p);
+                    }
+                }
+            }
+        }
+
+        let mut live_after = vec![BitSet::default(); func.num_insts()];
+        for b in &func.layout {
+            let mut live = live_out[b.index()].clone();
+            for i in func.block(*b).insts.iter().rev() {
+                live_after[i.index()] = live.clone();
+                step_back(&mut live, func.inst(*i));
+            }
+        }
+        Liveness {live_in, live_out, live_after}
+    }
+
+    pub fn live_in(&self, block : BlockId) -> &BitSet {
+        &self.live_in[block.index()]
+    }
+
+    pub fn live_out(&self, block : BlockId) -> &BitSet {
+        &self.live_out[block.index()]
+    }
+
+    /// Variables live just after `inst`. Empty for instructions that are not
+    /// in a block of the layout.
+    pub fn live_after(&self, inst : InstId) -> &BitSet {
+        &self.live_after[inst.index()]
+    }
+
+    /// Variables live just before `inst`.
+    pub fn live_before(&self, func : &Function, inst : InstId) -> BitSet {
+        let mut live = self.live_after(inst).clone();
+        step_back(&mut live, func.inst(inst));
+        live
+    }
+
+    /// The live range of every variable, indexed by `VarId`. Positions number
+    /// the instructions of the layout in order: instruction `k` reads its
+    /// arguments at `2k` and writes its destination at `2k + 1`, so a
+    /// variable that dies at an instruction does not overlap the one it
+    /// defines.
+    pub fn live_ranges(&self, func : &Function) -> Vec<LiveRange> {
+        let mut ranges : Vec<LiveRange> = (0..func.vars.len()).map(|v| LiveRange {
+            var : VarId::new(v),
+            intervals : vec![],
+            defs : vec![],
+            uses : vec![],
+        }).collect();
+        for (k, (_, i)) in func.insts().enumerate() {
+            let inst = func.inst(i);
+            for v in self.live_before(func, i).iter() {
+                ranges[v].extend(2 * k);
+            }
+            let mut after = self.live_after(i).clone();
+            if let Some(dest) = inst.dest {
+                after.insert(dest.index());
+                ranges[dest.index()].defs.push(i);
+            }
+            for v in after.iter() {
+                ranges[v].extend(2 * k + 1);
+            }
+            for a in &inst.args {
+                if ranges[a.index()].uses.last() != Some(&i) {
+                    ranges[a.index()].uses.push(i);
+                }
+            }
+        }
+        ranges
+    }
+}
+
+/// Where a variable is live, as sorted, disjoint, inclusive intervals of the
+/// positions described in `Liveness::live_ranges`, along with the
+/// instructions that define and use it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveRange {
+    pub var : VarId,
+    pub intervals : Vec<(usize, usize)>,
+    pub defs : Vec<InstId>,
+    pub uses : Vec<InstId>,
+}
+
+impl LiveRange {
+    fn extend(&mut self, pos : usize) {
+        match self.intervals.last_mut() {
+            Some((_, end)) if *end + 1 == pos => *end = pos,
+            _ => self.intervals.push((pos, pos)),
+        }
+    }
+
+    pub fn start(&self) -> Option<usize> {
+        self.intervals.first().map(|(s, _)| *s)
+    }
+
+    pub fn end(&self) -> Option<usize> {
+        self.intervals.last().map(|(_, e)| *e)
+    }
+
+    pub fn contains(&self, pos : usize) -> bool {
+        self.intervals.iter().any(|(s, e)| *s <= pos && pos <= *e)
+    }
+
+    pub fn overlaps(&self, other : &LiveRange) -> bool {
+        let (mut i, mut j) = (0, 0);
+        while i < self.intervals.len() && j < other.intervals.len() {
+            let (a, b) = (self.intervals[i], other.intervals[j]);
+            if a.0 <= b.1 && b.0 <= a.1 {
+                return true;
+            }
+            if a.1 < b.1 {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        false
+    }
+}
+
+/// Which variables may not share a register. Two variables interfere when
+/// one is written while the other is live, except that the destination of
+/// an `id` does not interfere with its argument. Those copies are kept in
+/// `moves` so that an allocator can try to coalesce them.
+pub struct InterferenceGraph {
+    adj : Vec<BitSet>,
+    pub moves : Vec<(VarId, VarId)>,
+}
+
+impl InterferenceGraph {
+    pub fn new(func : &Function, live : &Liveness) -> Self {
+        let vars = func.vars.len();
+        let mut graph = InterferenceGraph {adj : vec![BitSet::new(vars); vars], moves : vec![]};
+        // The arguments are all written on entry to the function.
+        if let Some(entry) = func.layout.first() {
+            for (a, _) in &func.args {
+                for v in live.live_in(*entry).iter() {
+                    graph.add_edge(*a, VarId::new(v));
+                }
+            }
+        }
+        for (_, i) in func.insts() {
+            let inst = func.inst(i);
+            let Some(dest) = inst.dest else {
+                continue;
+            };
+            let source = match (inst.op.as_str(), inst.args.as_slice()) {
+                ("id", [src]) => {
+                    graph.moves.push((dest, *src));
+                    Some(*src)
+                },
+                _ => None,
+            };
+            for v in live.live_after(i).iter() {
+                let v = VarId::new(v);
+                if Some(v) != source {
+                    graph.add_edge(dest, v);
+                }
+            }
+        }
+        graph
+    }
+
+    /// Adds an edge between `a` and `b`, unless they are the same variable.
+    pub fn add_edge(&mut self, a : VarId, b : VarId) {
+        if a != b {
+            self.adj[a.index()].insert(b.index());
+            self.adj[b.index()].insert(a.index());
+        }
+    }
+
+    pub fn interferes(&self, a : VarId, b : VarId) -> bool {
+        self.adj[a.index()].contains(b.index())
+    }
+
+    pub fn neighbors(&self, v : VarId) -> impl Iterator<Item=VarId> + '_ {
+        self.adj[v.index()].iter().map(VarId::new)
+    }
+
+    pub fn degree(&self, v : VarId) -> usize {
+        self.adj[v.index()].len()
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.adj.len()
+    }
+}
@@ -0,0 +1,255 @@
+use crate::cfg::Cfg;
+use crate::dominators::{form_dom_tree, get_reverse_post_order};
+use std::collections::{HashMap, HashSet};
+
+/// A natural loop. `latches` are the sources of the back edges into
+/// `header` and `exits` are the edges leaving the body, as
+/// `(inside, outside)` pairs. `parent` and `children` index into the
+/// owning `LoopForest`.
+#[derive(Debug, Clone)]
+pub struct Loop {
+    pub idx : usize,
+    pub header : i32,
+    pub body : HashSet<i32>,
+    pub latches : Vec<i32>,
+    pub exits : Vec<(i32, i32)>,
+    pub parent : Option<usize>,
+    pub children : Vec<usize>,
+    pub depth : usize,
+}
+
+impl Loop {
+    pub fn contains(&self, block : i32) -> bool {
+        self.body.contains(&block)
+    }
+
+    /// Blocks inside the loop with a successor outside it.
+    pub fn exiting_blocks(&self) -> Vec<i32> {
+        let mut blocks : Vec<i32> = self.exits.iter().map(|(from, _)| *from).collect();
+        blocks.sort();
+        blocks.dedup();
+        blocks
+    }
+
+    /// Blocks outside the loop with a predecessor inside it.
+    pub fn exit_blocks(&self) -> Vec<i32> {
+        let mut blocks : Vec<i32> = self.exits.iter().map(|(_, to)| *to).collect();
+        blocks.sort();
+        blocks.dedup();
+        blocks
+    }
+}
+
+/// A strongly connected region that is entered at more than one block, so it
+/// has no single header and is not a natural loop.
+#[derive(Debug, Clone)]
+pub struct IrreducibleRegion {
+    pub entries : Vec<i32>,
+    pub blocks : HashSet<i32>,
+}
+
+/// All natural loops of a function, nested by containment. The loop arena is
+/// ordered so that every loop comes after the loops that contain it.
+#[derive(Debug, Default, Clone)]
+pub struct LoopForest {
+    pub loops : Vec<Loop>,
+    pub roots : Vec<usize>,
+    pub back_edges : Vec<(i32, i32)>,
+    pub irreducible : Vec<IrreducibleRegion>,
+    innermost : HashMap<i32, usize>,
+}
+
+impl LoopForest {
+    pub fn get_loop(&self, idx : usize) -> &Loop {
+        &self.loops[idx]
+    }
+
+    /// The innermost loop containing `block`, if any.
+    pub fn loop_of(&self, block : i32) -> Option<&Loop> {
+        self.innermost.get(&block).map(|idx| &self.loops[*idx])
+    }
+
+    /// Number of loops containing `block`.
+    pub fn depth(&self, block : i32) -> usize {
+        self.loop_of(block).map(|l| l.depth).unwrap_or(0)
+    }
+
+    pub fn is_reducible(&self) -> bool {
+        self.irreducible.is_empty()
+    }
+
+    /// Loops ordered from the innermost outwards.
+    pub fn inner_to_outer(&self) -> Vec<usize> {
+        (0..self.loops.len()).rev().collect()
+    }
+}
+
+/// Edges `t -> h` where `h` dominates `t`, among blocks reachable from the
+/// entry.
+pub fn find_back_edges(cfg : &Cfg) -> Vec<(i32, i32)> {
+    let dom_tree = form_dom_tree(cfg);
+    let mut edges = vec![];
+    for t in get_reverse_post_order(cfg) {
+        for h in cfg.succ.get(&t).unwrap() {
+            if dom_tree.dominates(h, &t) {
+                edges.push((t, *h));
+            }
+        }
+    }
+    edges
+}
+
+fn natural_loop_body(cfg : &Cfg, header : i32, latches : &[i32]) -> HashSet<i32> {
+    let mut body = HashSet::new();
+    body.insert(header);
+    let mut stack : Vec<i32> = latches.to_vec();
+    while let Some(b) = stack.pop() {
+        if body.insert(b) {
+            stack.extend(cfg.pred.get(&b).unwrap());
+        }
+    }
+    body
+}
+
+fn loop_exits(cfg : &Cfg, body : &HashSet<i32>) -> Vec<(i32, i32)> {
+    let mut blocks : Vec<i32> = body.iter().copied().collect();
+    blocks.sort();
+    let mut exits = vec![];
+    for b in blocks {
+        for s in cfg.succ.get(&b).unwrap() {
+            if !body.contains(s) && !exits.contains(&(b, *s)) {
+                exits.push((b, *s));
+            }
+        }
+    }
+    exits
+}
+
+/// Strongly connected components of the blocks reachable from the entry.
+/// This is Tarjan's algorithm with an explicit stack of `(block, next
+/// successor)` frames in place of recursion, so that long chains of blocks
+/// cannot overflow the call stack.
+fn reachable_sccs(cfg : &Cfg) -> Vec<HashSet<i32>> {
+    let mut sccs = vec![];
+    let Some((&entry, _)) = cfg.block_map.first() else {
+        return sccs;
+    };
+    let mut index : HashMap<i32, usize> = HashMap::new();
+    let mut low : HashMap<i32, usize> = HashMap::new();
+    let mut stack : Vec<i32> = vec![];
+    let mut on_stack : HashSet<i32> = HashSet::new();
+    let mut frames : Vec<(i32, usize)> = vec![(entry, 0)];
+    index.insert(entry, 0);
+    low.insert(entry, 0);
+    stack.push(entry);
+    on_stack.insert(entry);
+
+    while let Some((v, i)) = frames.pop() {
+        if let Some(w) = cfg.succ.get(&v).unwrap().get(i) {
+            frames.push((v, i + 1));
+            if !index.contains_key(w) {
+                let n = index.len();
+                index.insert(*w, n);
+                low.insert(*w, n);
+                stack.push(*w);
+                on_stack.insert(*w);
+                frames.push((*w, 0));
+            } else if on_stack.contains(w) {
+                let l = low[&v].min(index[w]);
+                low.insert(v, l);
+            }
+            continue;
+        }
+        // Every successor of `v` is done, so its low link is final.
+        if let Some((parent, _)) = frames.last() {
+            let l = low[parent].min(low[&v]);
+            low.insert(*parent, l);
+        }
+        if low[&v] == index[&v] {
+            let mut scc = HashSet::new();
+            while let Some(w) = stack.pop() {
+                on_stack.remove(&w);
+                scc.insert(w);
+                if w == v {
+                    break;
+                }
+            }
+            sccs.push(scc);
+        }
+    }
+    sccs
+}
+
+/// Regions containing a retreating edge whose target does not dominate its
+/// source. Each is reported as the strongly connected component holding it.
+fn find_irreducible(cfg : &Cfg, back_edges : &[(i32, i32)]) -> Vec<IrreducibleRegion> {
+    let rpo = get_reverse_post_order(cfg);
+    let order : HashMap<i32, usize> = rpo.iter().enumerate().map(|(i, b)| (*b, i)).collect();
+    let sccs = reachable_sccs(cfg);
+    let (&entry, _) = cfg.block_map.first().unwrap();
+    let mut regions : Vec<IrreducibleRegion> = vec![];
+    let mut seen = HashSet::new();
+
+    for t in &rpo {
+        for h in cfg.succ.get(t).unwrap() {
+            let retreating = order.get(h).unwrap() <= order.get(t).unwrap();
+            if !retreating || back_edges.contains(&(*t, *h)) {
+                continue;
+            }
+            let scc_idx = sccs.iter().position(|scc| scc.contains(t)).unwrap();
+            if !seen.insert(scc_idx) {
+                continue;
+            }
+            let blocks = sccs[scc_idx].clone();
+            let mut entries : Vec<i32> = blocks.iter().copied().filter(|b| {
+                *b == entry || cfg.pred.get(b).unwrap().iter().any(|p| !blocks.contains(p))
+            }).collect();
+            entries.sort();
+            regions.push(IrreducibleRegion {entries, blocks});
+        }
+    }
+    regions
+}
+
+pub fn find_loops(cfg : &Cfg) -> LoopForest {
+    let back_edges = find_back_edges(cfg);
+
+    let mut headers : Vec<i32> = vec![];
+    let mut latches : HashMap<i32, Vec<i32>> = HashMap::new();
+    for (t, h) in &back_edges {
+        if !headers.contains(h) {
+            headers.push(*h);
+        }
+        latches.entry(*h).or_default().push(*t);
+    }
+
+    let mut loops : Vec<Loop> = headers.into_iter().map(|h| {
+        let latches = latches.remove(&h).unwrap();
+        let body = natural_loop_body(cfg, h, &latches);
+        let exits = loop_exits(cfg, &body);
+        Loop {idx : 0, header : h, body, latches, exits, parent : None, children : vec![], depth : 1}
+    }).collect();
+
+    // Outer loops are strictly larger than the loops nested inside them.
+    loops.sort_by_key(|l| std::cmp::Reverse(l.body.len()));
+    for i in 0..loops.len() {
+        loops[i].idx = i;
+        let parent = (0..i).rev().find(|j| loops[*j].contains(loops[i].header));
+        if let Some(p) = parent {
+            loops[i].parent = Some(p);
+            loops[i].depth = loops[p].depth + 1;
+            loops[p].children.push(i);
+        }
+    }
+
+    let mut innermost = HashMap::new();
+    for l in &loops {
+        for b in &l.body {
+            innermost.insert(*b, l.idx);
+        }
+    }
+
+    let roots = loops.iter().filter(|l| l.parent.is_none()).map(|l| l.idx).collect();
+    let irreducible = find_irreducible(cfg, &back_edges);
+    LoopForest {loops, roots, back_edges, irreducible, innermost}
+}
@@ -0,0 +1,648 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use bril_rs::{AbstractFunction, AbstractCode, AbstractInstruction, AbstractType, ConstOps, Literal};
+use crate::form_blocks::*;
+
+#[derive(PartialEq, Eq, Hash, Clone)]
+pub(crate) struct ValueExpr {
+    op_code : String,
+    args : Vec<i32>,
+}
+
+impl ValueExpr {
+    pub(crate) fn new(op_code : String, args : Vec<i32>) -> ValueExpr {
+        ValueExpr {op_code, args}
+    }
+}
+
+/// How value numbering may treat the result of an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Purity {
+    /// Depends only on the arguments, so equal expressions have equal values.
+    Pure,
+    /// Reads memory, so equal expressions have equal values until the next
+    /// instruction that may write memory.
+    Load,
+    /// Gives a new value every time it runs.
+    Opaque,
+}
+
+pub(crate) fn purity(op : &str) -> Purity {
+    match op {
+        "id" | "add" | "mul" | "sub" | "div" | "eq" | "lt" | "gt" | "le" | "ge"
+        | "not" | "and" | "or" | "fadd" | "fmul" | "fsub" | "fdiv"
+        | "feq" | "flt" | "fgt" | "fle" | "fge" | "ptradd" => Purity::Pure,
+        "load" => Purity::Load,
+        _ => Purity::Opaque,
+    }
+}
+
+pub(crate) fn is_commutative(op : &str) -> bool {
+    matches!(op, "add" | "mul" | "and" | "or" | "eq" | "fadd" | "fmul" | "feq")
+}
+
+/// Whether an instruction may write memory.
+fn clobbers_memory(op : &str) -> bool {
+    matches!(op, "store" | "free" | "call")
+}
+
+#[derive(Default)]
+struct LvnTable {
+    vector : Vec<(Option<ValueExpr>, String)>,
+    map : HashMap<ValueExpr, (i32, String)>,
+}
+
+impl LvnTable {
+    fn add_value(&mut self, value_expr : Option<ValueExpr>, home : &String) -> i32 {
+        let val_num : i32 = self.vector.len().try_into().unwrap();
+        self.vector.push((value_expr.clone(), home.to_string()));
+        if let Some(expr) = value_expr {
+            self.map.insert(expr, (val_num, home.to_string()));
+        }
+        val_num
+    }
+
+    /// Moves the values whose home is `var` to another variable that holds
+    /// them, before `var` is overwritten with value `next`. A value that no
+    /// other variable holds loses its home and can no longer be reused,
+    /// unless it is `next` itself, as in `x = id x`, and stays with `var`.
+    fn evict(&mut self, var : &str, var2num : &HashMap<String, i32>, next : Option<i32>) {
+        for (num, (value_expr, home)) in self.vector.iter_mut().enumerate() {
+            let num : i32 = num.try_into().unwrap();
+            if home != var || Some(num) == next {
+                continue;
+            }
+            let holder = var2num.iter()
+                .filter(|(v, n)| **n == num && v.as_str() != var)
+                .map(|(v, _)| v.to_string())
+                .min();
+            if let Some(expr) = value_expr {
+                if self.map.get(expr).map(|(n, _)| *n) == Some(num) {
+                    match &holder {
+                        Some(holder) => self.map.insert(expr.clone(), (num, holder.to_string())),
+                        None => self.map.remove(expr),
+                    };
+                }
+            }
+            *home = holder.unwrap_or_default();
+        }
+    }
+
+    /// The variable holding value `num`, if any still does.
+    fn home(&self, num : i32) -> Option<String> {
+        match self.lookup_value(num) {
+            Some((_, home)) if !home.is_empty() => Some(home),
+            _ => None,
+        }
+    }
+
+    /// Drops the numbered loads, whose memory may have changed.
+    fn forget_loads(&mut self) {
+        self.map.retain(|expr, _| purity(&expr.op_code) != Purity::Load);
+    }
+
+    fn next_value(&self) -> i32 {
+        self.vector.len().try_into().unwrap()
+    }
+
+    fn lookup_value(&self, val : i32) -> Option<(Option<ValueExpr>, String)> {
+        self.vector.get(usize::try_from(val).unwrap()).cloned()
+    }
+
+    fn lookup_expr(&self, value_expr : &ValueExpr, prop : bool) -> Option<(i32, String)> {
+        if prop &&  value_expr.op_code == "id"{
+            let val = value_expr.args[0];
+            self.lookup_value(val).map(|(_, var)| (val, var))
+        } else {
+            self.map.get(value_expr).cloned()
+        }
+    }
+
+    fn contains_expr(&self, value_expr : &ValueExpr, prop : bool) -> bool {
+        if prop && value_expr.op_code == "id" {
+            let val = value_expr.args[0];
+            self.vector.get(usize::try_from(val).unwrap()).is_some()
+        } else {
+            self.map.contains_key(value_expr)
+        }
+    }
+}
+
+fn copy_instr(instr : AbstractInstruction, home_var : &String) -> AbstractCode {
+    let new_instr = match instr {
+        AbstractInstruction::Value {op_type, dest, funcs, labels, ..} => {
+            AbstractInstruction::Value {op : "id".to_string(), args : vec![home_var.to_string()],
+                                        op_type, dest, funcs, labels}
+        },
+        _ => instr,
+    };
+    AbstractCode::Instruction(new_instr)
+}
+
+fn reform_instr(mut instr : AbstractInstruction, dest : Option<String>,
+    table : &LvnTable, var2num : &HashMap<String, i32>) -> AbstractCode {
+    match &mut instr {
+        AbstractInstruction::Value {args, ..} | AbstractInstruction::Effect {args, ..} => {
+            for a in args.iter_mut() {
+                let &num = var2num.get(a).unwrap();
+                *a = table.home(num).expect("an argument's value has no home");
+            }
+        },
+        _ => (),
+    };
+    let new_instr = match dest {
+        Some(dest) => {
+            match instr {
+                AbstractInstruction::Value {args, op, op_type, labels, funcs, ..} => {
+                    AbstractInstruction::Value{args, op, op_type, labels, funcs, dest}
+                },
+                AbstractInstruction::Constant {op, const_type, value, ..} => {
+                    AbstractInstruction::Constant {op, const_type, value, dest}
+                },
+                x => x,
+            }
+        },
+        None => instr,
+    };
+    AbstractCode::Instruction(new_instr)
+}
+
+fn overwritten_later(instrs : &[AbstractCode]) -> Vec<bool> {
+    let mut output = vec![true; instrs.len()];
+    let mut seen = HashSet::new();
+    for (i, code) in instrs.iter().enumerate().rev() {
+        if let AbstractCode::Instruction(AbstractInstruction::Value {dest, ..}
+                                         | AbstractInstruction::Constant {dest, ..}) = code {
+            if seen.insert(dest) {
+                output[i] = false;
+            }
+        }
+    }
+    output
+}
+
+fn block_inputs(instrs : &[AbstractCode]) -> HashSet<String> {
+    let mut read : HashSet<String> = HashSet::new();
+    let mut written : HashSet<&String> = HashSet::new();
+    for code in instrs {
+        if let AbstractCode::Instruction(instr) = code {
+            match instr {
+                AbstractInstruction::Value {args, ..}
+                | AbstractInstruction::Effect {args, ..} => {
+                    for a in args.iter().collect::<HashSet<&String>>().difference(&written) {
+                        read.insert(a.to_string());
+                    }
+                }
+                _ => (),
+            }
+            match instr {
+                AbstractInstruction::Value {dest, ..}
+                | AbstractInstruction::Constant {dest, ..} => {
+                    written.insert(dest);
+                },
+                _ => (),
+            }
+        }
+    }
+    read
+}
+
+fn new_var(val : i32) -> String {
+    format!("lvn.{}", val)
+}
+
+fn get_dest(table : &LvnTable, dest : &String, overwritten_later : bool) -> String {
+    if overwritten_later {
+        new_var(table.next_value())
+    } else {
+        dest.to_string()
+    }
+}
+
+fn const_instr(instr : AbstractInstruction, new_dest : Option<String>, value : Literal) -> AbstractCode {
+    let (dest, const_type) = match instr {
+        AbstractInstruction::Value {dest, op_type, ..} => 
+            if let Some(new_dest) = new_dest {
+                (new_dest, op_type)
+            } else {
+                (dest, op_type)
+            },
+        AbstractInstruction::Constant {dest, const_type, ..} =>
+            if let Some(new_dest) = new_dest {
+                (new_dest, const_type)
+            } else {
+                (dest, const_type)
+            },
+        _ => panic!("Cannot convert effect into const"),
+    };
+    let new_instr = AbstractInstruction::Constant {op : ConstOps::Const, dest, const_type, value};
+    AbstractCode::Instruction(new_instr)
+}
+
+fn binop_int(a : &Literal, b : &Literal, func : &dyn Fn(&i64, &i64) -> i64) -> Option<Literal> {
+    match a {
+        Literal::Int(x) => {
+            if let Literal::Int(y) = b {
+                Some(Literal::Int(func(x, y)))
+            } else {
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+fn binop_float(a : &Literal, b : &Literal, func : &dyn Fn(&f64, &f64) -> f64) -> Option<Literal> {
+    match a {
+        Literal::Float(x) => {
+            if let Literal::Float(y) = b {
+                // Bril's JSON form cannot represent infinities or NaN.
+                Some(func(x, y)).filter(|z| z.is_finite()).map(Literal::Float)
+            } else {
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+fn binop_bool(a : &Literal, b : &Literal, func : &dyn Fn(&bool, &bool) -> bool) -> Option<Literal> {
+    match a {
+        Literal::Bool(x) => {
+            if let Literal::Bool(y) = b {
+                Some(Literal::Bool(func(x, y)))
+            } else {
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+fn cmp_int(a : &Literal, b : &Literal, func : &dyn Fn(&i64, &i64) -> bool) -> Option<Literal> {
+    match (a, b) {
+        (Literal::Int(x), Literal::Int(y)) => Some(Literal::Bool(func(x, y))),
+        _ => None,
+    }
+}
+
+fn cmp_float(a : &Literal, b : &Literal, func : &dyn Fn(&f64, &f64) -> bool) -> Option<Literal> {
+    match (a, b) {
+        (Literal::Float(x), Literal::Float(y)) => Some(Literal::Bool(func(x, y))),
+        _ => None,
+    }
+}
+
+fn apply_fold(op : &String, a : &Literal, b : Option<&Literal>) -> Option<Literal> {
+    match op.to_string().as_str() {
+        "id"   => Some(a.clone()),
+        "add"  => binop_int(a, b?, &|a, b| a.wrapping_add(*b)),
+        "mul"  => binop_int(a, b?, &|a, b| a.wrapping_mul(*b)),
+        "sub"  => binop_int(a, b?, &|a, b| a.wrapping_sub(*b)),
+        // Division by zero is left for the program to trap on.
+        "div"  => match b? {
+            Literal::Int(0) => None,
+            b => binop_int(a, b, &|a, b| a.wrapping_div(*b)),
+        },
+        "fadd" => binop_float(a, b?, &|a, b| a + b),
+        "fmul" => binop_float(a, b?, &|a, b| a * b),
+        "fsub" => binop_float(a, b?, &|a, b| a - b),
+        "fdiv" => binop_float(a, b?, &|a, b| a / b),
+        "eq"   => cmp_int(a, b?, &|a, b| a == b),
+        "lt"   => cmp_int(a, b?, &|a, b| a < b),
+        "gt"   => cmp_int(a, b?, &|a, b| a > b),
+        "le"   => cmp_int(a, b?, &|a, b| a <= b),
+        "ge"   => cmp_int(a, b?, &|a, b| a >= b),
+        "feq"  => cmp_float(a, b?, &|a, b| a == b),
+        "flt"  => cmp_float(a, b?, &|a, b| a < b),
+        "fgt"  => cmp_float(a, b?, &|a, b| a > b),
+        "fle"  => cmp_float(a, b?, &|a, b| a <= b),
+        "fge"  => cmp_float(a, b?, &|a, b| a >= b),
+        "and"  => binop_bool(a, b?, &|a, b| *a && *b),
+        "or"   => binop_bool(a, b?, &|a, b| *a || *b),
+        "not"  => 
+            match a {
+                Literal::Bool(x) => Some(Literal::Bool(!x)),
+                _ => None,
+            },
+        _ => None,
+    }
+}
+
+/// Folds `and`/`or` with a constant argument that decides the result.
+fn fold_partial(num2const : &HashMap<i32, Literal>, value_expr : &ValueExpr) -> Option<Literal> {
+    let args = &value_expr.args;
+    match value_expr.op_code.as_str() {
+        "and" | "or" => {
+            let decides = value_expr.op_code == "or";
+            let known = args.iter().any(|a| num2const.get(a) == Some(&Literal::Bool(decides)));
+            if known { Some(Literal::Bool(decides)) } else { None }
+        },
+        _ => None,
+    }
+}
+
+fn const_fold(num2const : &mut HashMap<i32, Literal>, value_expr : &ValueExpr, 
+    num : i32, fold : bool) -> Option<Literal> {
+    if !fold || value_expr.args.is_empty() {
+        return None;
+    }
+    let consts : Vec<&Literal> = value_expr.args.iter()
+        .filter_map(|a| num2const.get(a)).collect();
+    let c = if consts.len() == value_expr.args.len() {
+        apply_fold(&value_expr.op_code, consts[0], consts.get(1).copied())
+    } else {
+        fold_partial(num2const, value_expr)
+    };
+    if let Some(c) = &c {
+        num2const.insert(num, c.clone());
+    }
+    c
+}
+
+/// What an algebraic identity turns an expression into.
+enum Simplified {
+    Const(Literal),
+    /// The value with this number.
+    Copy(i32),
+}
+
+/// An algebraic identity that `simplify` may apply, named as on the command
+/// line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Identity {
+    /// `x + 0 = x`
+    AddZero,
+    /// `x - 0 = x`
+    SubZero,
+    /// `x - x = 0`
+    SubSelf,
+    /// `x * 0 = 0`
+    MulZero,
+    /// `x * 1 = x`
+    MulOne,
+    /// `x / 1 = x`
+    DivOne,
+    /// `x == x`, `x <= x` and `x >= x` are true, `x < x` and `x > x` false.
+    CmpSelf,
+    /// `x and x = x or x = x`
+    LogicSelf,
+    /// `x and true = x` and `x or false = x`
+    LogicConst,
+    /// `not (not x) = x`
+    NotNot,
+}
+
+impl Identity {
+    pub const ALL : [Identity; 10] = [Identity::AddZero, Identity::SubZero, Identity::SubSelf,
+        Identity::MulZero, Identity::MulOne, Identity::DivOne, Identity::CmpSelf,
+        Identity::LogicSelf, Identity::LogicConst, Identity::NotNot];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Identity::AddZero => "add-zero",
+            Identity::SubZero => "sub-zero",
+            Identity::SubSelf => "sub-self",
+            Identity::MulZero => "mul-zero",
+            Identity::MulOne => "mul-one",
+            Identity::DivOne => "div-one",
+            Identity::CmpSelf => "cmp-self",
+            Identity::LogicSelf => "logic-self",
+            Identity::LogicConst => "logic-const",
+            Identity::NotNot => "not-not",
+        }
+    }
+}
+
+impl FromStr for Identity {
+    type Err = String;
+
+    fn from_str(s : &str) -> Result<Self, Self::Err> {
+        Identity::ALL.into_iter().find(|i| i.name() == s).ok_or_else(|| format!("unknown identity `{s}`"))
+    }
+}
+
+/// Parses a comma-separated list of identity names, where `all` stands for
+/// every identity.
+pub fn parse_identities(text : &str) -> Result<HashSet<Identity>, String> {
+    let mut rules = HashSet::new();
+    for name in text.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        if name == "all" {
+            rules.extend(Identity::ALL);
+        } else {
+            rules.insert(name.parse()?);
+        }
+    }
+    Ok(rules)
+}
+
+/// Applies the algebraic identities in `rules` that hold when only some
+/// arguments are constants. Float operations are left alone, since
+/// identities like `x + 0.0 = x` do not hold for every float.
+fn simplify(table : &LvnTable, num2const : &HashMap<i32, Literal>, value_expr : &ValueExpr,
+    rules : &HashSet<Identity>) -> Option<Simplified> {
+    let args = &value_expr.args;
+    let int = |i : usize| match num2const.get(&args[i]) {
+        Some(Literal::Int(v)) => Some(*v),
+        _ => None,
+    };
+    let boolean = |i : usize| match num2const.get(&args[i]) {
+        Some(Literal::Bool(v)) => Some(*v),
+        _ => None,
+    };
+    let on = |rule : Identity| rules.contains(&rule);
+    let same = args.len() == 2 && args[0] == args[1];
+    match value_expr.op_code.as_str() {
+        "add" if on(Identity::AddZero) && int(1) == Some(0) => Some(Simplified::Copy(args[0])),
+        "add" if on(Identity::AddZero) && int(0) == Some(0) => Some(Simplified::Copy(args[1])),
+        "sub" if on(Identity::SubZero) && int(1) == Some(0) => Some(Simplified::Copy(args[0])),
+        "sub" if on(Identity::SubSelf) && same => Some(Simplified::Const(Literal::Int(0))),
+        "mul" if on(Identity::MulZero) && (int(0) == Some(0) || int(1) == Some(0)) =>
+            Some(Simplified::Const(Literal::Int(0))),
+        "mul" if on(Identity::MulOne) && int(1) == Some(1) => Some(Simplified::Copy(args[0])),
+        "mul" if on(Identity::MulOne) && int(0) == Some(1) => Some(Simplified::Copy(args[1])),
+        "div" if on(Identity::DivOne) && int(1) == Some(1) => Some(Simplified::Copy(args[0])),
+        "eq" | "le" | "ge" if on(Identity::CmpSelf) && same => Some(Simplified::Const(Literal::Bool(true))),
+        "lt" | "gt" if on(Identity::CmpSelf) && same => Some(Simplified::Const(Literal::Bool(false))),
+        "and" | "or" if on(Identity::LogicSelf) && same => Some(Simplified::Copy(args[0])),
+        "and" if on(Identity::LogicConst) && boolean(1) == Some(true) => Some(Simplified::Copy(args[0])),
+        "and" if on(Identity::LogicConst) && boolean(0) == Some(true) => Some(Simplified::Copy(args[1])),
+        "or" if on(Identity::LogicConst) && boolean(1) == Some(false) => Some(Simplified::Copy(args[0])),
+        "or" if on(Identity::LogicConst) && boolean(0) == Some(false) => Some(Simplified::Copy(args[1])),
+        "not" if on(Identity::NotNot) => match table.lookup_value(args[0]) {
+            Some((Some(inner), _)) if inner.op_code == "not" && table.home(inner.args[0]).is_some() =>
+                Some(Simplified::Copy(inner.args[0])),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn add_instr(dest : String, arg : &str, op_type : Option<AbstractType>) -> AbstractInstruction {
+    AbstractInstruction::Value {
+        op : "add".to_string(),
+        args : vec![arg.to_string(), arg.to_string()],
+        dest,
+        funcs : vec![],
+        labels : vec![],
+        op_type,
+    }
+}
+
+/// Rewrites `mul x c`, where `c` is a constant `2^k` with `k > 0`, into
+/// `add t t` where `t` is `x` doubled `k - 1` times. Bril has no shifts, so
+/// each doubling is an `add` that is numbered and emitted into `block` unless
+/// the block already computes it.
+fn strength_reduce(instr : AbstractInstruction, table : &mut LvnTable, var2num : &mut HashMap<String, i32>,
+    num2const : &HashMap<i32, Literal>, block : &mut Block) -> AbstractInstruction {
+    let (args, op_type) = match &instr {
+        AbstractInstruction::Value {op, args, op_type, ..} if op == "mul" && args.len() == 2 => (args, op_type),
+        _ => return instr,
+    };
+    let nums : Vec<i32> = args.iter().map(|a| *var2num.get(a).unwrap()).collect();
+    let power = |i : usize| match num2const.get(&nums[i]) {
+        Some(Literal::Int(c)) if *c > 1 && c.count_ones() == 1 => Some(c.trailing_zeros()),
+        _ => None,
+    };
+    let (k, mut num) = match (power(0), power(1)) {
+        (_, Some(k)) if !num2const.contains_key(&nums[0]) => (k, nums[0]),
+        (Some(k), _) if !num2const.contains_key(&nums[1]) => (k, nums[1]),
+        _ => return instr,
+    };
+    for _ in 1..k {
+        let expr = ValueExpr::new("add".to_string(), vec![num, num]);
+        num = match table.lookup_expr(&expr, false) {
+            Some((n, _)) => n,
+            None => {
+                let arg = table.home(num).unwrap();
+                let home = new_var(table.next_value());
+                block.instrs.push(AbstractCode::Instruction(add_instr(home.to_string(), &arg, op_type.clone())));
+                table.add_value(Some(expr), &home)
+            },
+        };
+    }
+    let arg = table.home(num).unwrap();
+    var2num.insert(arg.to_string(), num);
+    match instr {
+        AbstractInstruction::Value {dest, op_type, ..} => add_instr(dest, &arg, op_type),
+        _ => unreachable!(),
+    }
+}
+
+fn lvn_pass(block : Block, prop : bool, comm : bool, fold : bool, algebra : &HashSet<Identity>, strength : bool) -> Block {
+    let mut new_block = Block {instrs : Vec::new()};
+    let mut table = LvnTable::default();
+    let mut var2num : HashMap<String, i32> = HashMap::new();
+    let mut num2const : HashMap<i32, Literal> = HashMap::new();
+    for var in block_inputs(&block.instrs) {
+        let num = table.add_value(None, &var);
+        var2num.insert(var, num);
+    }
+    for (overwritten_later, code) in overwritten_later(&block.instrs).into_iter().zip(block.instrs) {
+        match code {
+            AbstractCode::Instruction(instr) => {
+                let instr = if strength {
+                    strength_reduce(instr, &mut table, &mut var2num, &num2const, &mut new_block)
+                } else {
+                    instr
+                };
+                match &instr {
+                    AbstractInstruction::Value {op, args, dest, ..} if purity(op) != Purity::Opaque => {
+                        let mut arg_vals : Vec<i32> = args.iter()
+                                                      .map(|a| *var2num.get(a).unwrap())
+                                                      .collect();
+                        if comm && is_commutative(op) {
+                            arg_vals.sort();
+                        }
+                        let mut value_expr = ValueExpr::new(op.to_string(), arg_vals);
+                        // `ptradd p 0` is a copy of `p`.
+                        if fold && op == "ptradd" && num2const.get(&value_expr.args[1]) == Some(&Literal::Int(0)) {
+                            value_expr = ValueExpr::new("id".to_string(), vec![value_expr.args[0]]);
+                        }
+                        let simplified = simplify(&table, &num2const, &value_expr, algebra);
+                        if let Some(Simplified::Const(constant)) = simplified {
+                            let new_dest = get_dest(&table, dest, overwritten_later);
+                            let dest = dest.to_string();
+                            new_block.instrs.push(const_instr(instr, Some(new_dest.to_string()), constant.clone()));
+                            table.evict(&new_dest, &var2num, None);
+                            let num = table.add_value(None, &new_dest);
+                            num2const.insert(num, constant);
+                            var2num.insert(dest, num);
+                        } else if let Some(Simplified::Copy(num)) = simplified {
+                            let home = table.home(num).unwrap();
+                            table.evict(dest, &var2num, Some(num));
+                            var2num.insert(dest.to_string(), num);
+                            new_block.instrs.push(copy_instr(instr, &home));
+                        } else if table.contains_expr(&value_expr, prop) {
+                            let (num, var) = table.lookup_expr(&value_expr, prop).unwrap();
+                            let c = const_fold(&mut num2const, &value_expr, num, fold);
+                            table.evict(dest, &var2num, Some(num));
+                            var2num.insert(dest.to_string(), num);
+                            match c {
+                                Some(constant) => new_block.instrs.push(const_instr(instr, None, constant)),
+                                None => new_block.instrs.push(copy_instr(instr, &var)),
+                            }
+                        } else {
+                            let new_dest = get_dest(&table, dest, overwritten_later);
+                            let num = table.next_value();
+                            let c = const_fold(&mut num2const, &value_expr, num, fold);
+                            let dest = dest.to_string();
+                            // Rename the arguments before `new_dest` is overwritten.
+                            let new_code = match &c {
+                                Some(constant) => const_instr(instr, Some(new_dest.to_string()), constant.clone()),
+                                None => reform_instr(instr, Some(new_dest.to_string()), &table, &var2num),
+                            };
+                            new_block.instrs.push(new_code);
+                            table.evict(&new_dest, &var2num, None);
+                            let num = table.add_value(Some(value_expr), &new_dest);
+                            if c.is_some() {
+                                var2num.insert(new_dest.to_string(), num);
+                            }
+                            var2num.insert(dest, num);
+                        }
+                    },
+                    AbstractInstruction::Value {op, dest, ..} => {
+                        if clobbers_memory(op) {
+                            table.forget_loads();
+                        }
+                        let dest = dest.to_string();
+                        let new_dest = get_dest(&table, &dest, overwritten_later);
+                        new_block.instrs.push(reform_instr(instr, Some(new_dest.to_string()), &table, &var2num));
+                        table.evict(&new_dest, &var2num, None);
+                        let num = table.add_value(None, &new_dest);
+                        var2num.insert(dest, num);
+                    },
+                    AbstractInstruction::Constant {dest, value, ..} => {
+                        let new_dest = get_dest(&table, dest, overwritten_later);
+                        table.evict(&new_dest, &var2num, None);
+                        let num = table.add_value(None, &new_dest);
+                        var2num.insert(dest.to_string(), num);
+                        num2const.insert(num, value.clone());
+                        new_block.instrs.push(reform_instr(instr, Some(new_dest), &table, &var2num));
+                    },
+                    AbstractInstruction::Effect {op, ..} => {
+                        if clobbers_memory(op) {
+                            table.forget_loads();
+                        }
+                        new_block.instrs.push(reform_instr(instr, None, &table, &var2num));
+                    },
+                }
+            },
+            AbstractCode::Label {..} => new_block.instrs.push(code),
+        } 
+    }
+    new_block
+}
+
+/// Local value numbering. `prop` enables copy propagation, `comm` treats
+/// commutative operations with swapped arguments as equal, `fold` folds
+/// constants, `algebra` is the set of algebraic identities to apply and
+/// `strength` turns multiplications by a power of two into additions.
+pub fn local_value_numbering(func : &mut AbstractFunction, prop : bool, comm : bool, fold : bool,
+    algebra : &HashSet<Identity>, strength : bool) {
+    let blocks = form_blocks(func);
+    let new_blocks = blocks.into_iter()
+                           .map(|b| lvn_pass(b, prop, comm, fold, algebra, strength))
+                           .collect();
+    func.instrs = flatten_blocks(new_blocks);
+}
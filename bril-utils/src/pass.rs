@@ -0,0 +1,329 @@
+use std::collections::{HashMap, HashSet};
+use bril_rs::{AbstractFunction, AbstractProgram};
+use indexmap::IndexMap;
+use crate::verify::verify_function_against;
+use crate::lvn::{local_value_numbering, Identity};
+use crate::gvn::global_value_numbering;
+use crate::ssa::{to_ssa_mode, from_ssa, SsaMode};
+
+/// A transformation of a single function. Returns whether it changed the
+/// function, which is what fixpoint groups use to decide when to stop.
+pub trait Pass {
+    fn run(&self, func : &mut AbstractFunction) -> bool;
+}
+
+impl<F : Fn(&mut AbstractFunction) -> bool> Pass for F {
+    fn run(&self, func : &mut AbstractFunction) -> bool {
+        self(func)
+    }
+}
+
+/// Adapts a pass that does not report changes by comparing the function's
+/// instructions before and after it runs.
+pub fn compare_instrs(pass : impl Fn(&mut AbstractFunction)) -> impl Fn(&mut AbstractFunction) -> bool {
+    move |func| {
+        let before = func.instrs.clone();
+        pass(func);
+        func.instrs != before
+    }
+}
+
+/// The options of one pass in a pipeline, e.g. `prop` and `fold` in
+/// `lvn[prop,fold]`, or `mode` in `ssa[mode=minimal]`.
+#[derive(Debug, Clone, Default)]
+pub struct PassOptions {
+    values : HashMap<String, Option<String>>,
+}
+
+impl PassOptions {
+    pub fn flag(&self, name : &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    pub fn value(&self, name : &str) -> Option<&str> {
+        self.values.get(name)?.as_deref()
+    }
+}
+
+pub type MakePass = fn(&PassOptions) -> Result<Box<dyn Pass>, String>;
+
+pub struct PassInfo {
+    pub name : &'static str,
+    pub description : &'static str,
+    /// The option names the pass accepts; anything else is rejected when
+    /// the pipeline is parsed.
+    pub options : &'static [&'static str],
+    pub make : MakePass,
+}
+
+/// Maps pass names to constructors, in registration order.
+#[derive(Default)]
+pub struct Registry {
+    passes : IndexMap<&'static str, PassInfo>,
+}
+
+/// How many times a fixpoint group may repeat before we give up on it.
+const MAX_FIXPOINT_ITERATIONS : usize = 100;
+
+pub enum Step {
+    Pass { name : String, pass : Box<dyn Pass> },
+    /// `fix(a,b,...)`: repeat the steps until none of them changes anything.
+    Fixpoint(Vec<Step>),
+}
+
+pub struct Pipeline {
+    pub steps : Vec<Step>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunOptions {
+    /// Print each function to stderr after every pass that runs on it.
+    pub print_after : bool,
+    /// Run the verifier after every pass and stop at the first diagnostic.
+    pub verify_each : bool,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, info : PassInfo) {
+        self.passes.insert(info.name, info);
+    }
+
+    pub fn passes(&self) -> impl Iterator<Item=&PassInfo> {
+        self.passes.values()
+    }
+
+    /// Parses a pipeline such as `fix(lvn[prop,fold],tdce),licm`:
+    ///
+    /// ```text
+    /// pipeline := step (',' step)*
+    /// step     := 'fix' '(' pipeline ')' | name ('[' option (',' option)* ']')?
+    /// option   := name ('=' name)?
+    /// ```
+    pub fn parse(&self, text : &str) -> Result<Pipeline, String> {
+        let mut parser = Parser {registry : self, chars : text.chars().filter(|c| !c.is_whitespace()).collect(), pos : 0};
+        let steps = parser.pipeline()?;
+        match parser.peek() {
+            None => Ok(Pipeline {steps}),
+            Some(c) => Err(format!("unexpected `{c}` at {}", parser.pos)),
+        }
+    }
+
+    fn make(&self, name : &str, options : PassOptions) -> Result<Box<dyn Pass>, String> {
+        let info = self.passes.get(name).ok_or_else(|| format!("unknown pass `{name}`"))?;
+        let mut keys : Vec<&String> = options.values.keys().collect();
+        keys.sort();
+        if let Some(k) = keys.into_iter().find(|k| !info.options.contains(&k.as_str())) {
+            return Err(format!("unknown option `{k}` for pass `{name}`"));
+        }
+        (info.make)(&options).map_err(|e| format!("{name}: {e}"))
+    }
+}
+
+struct Parser<'a> {
+    registry : &'a Registry,
+    chars : Vec<char>,
+    pos : usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c : char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c : char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(format!("expected `{c}` at {}", self.pos))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(format!("expected a name at {start}"));
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn pipeline(&mut self) -> Result<Vec<Step>, String> {
+        let mut steps = vec![self.step()?];
+        while self.eat(',') {
+            steps.push(self.step()?);
+        }
+        Ok(steps)
+    }
+
+    fn step(&mut self) -> Result<Step, String> {
+        let name = self.name()?;
+        if name == "fix" && self.eat('(') {
+            let steps = self.pipeline()?;
+            self.expect(')')?;
+            return Ok(Step::Fixpoint(steps));
+        }
+        let mut options = PassOptions::default();
+        if self.eat('[') {
+            loop {
+                let key = self.name()?;
+                let value = if self.eat('=') { Some(self.name()?) } else { None };
+                options.values.insert(key, value);
+                if !self.eat(',') {
+                    break;
+                }
+            }
+            self.expect(']')?;
+        }
+        let pass = self.registry.make(&name, options)?;
+        Ok(Step::Pass {name, pass})
+    }
+}
+
+impl Pipeline {
+    /// Runs the pipeline on every function of `program`. Returns whether
+    /// anything changed, or the diagnostics that stopped it under
+    /// `verify_each`.
+    pub fn run(&self, program : &mut AbstractProgram, options : RunOptions) -> Result<bool, String> {
+        let signatures = program.clone();
+        let mut changed = false;
+        for func in &mut program.functions {
+            changed |= run_steps(&self.steps, func, &signatures, options)?;
+        }
+        Ok(changed)
+    }
+}
+
+fn run_steps(steps : &[Step], func : &mut AbstractFunction, signatures : &AbstractProgram,
+             options : RunOptions) -> Result<bool, String> {
+    let mut changed = false;
+    for step in steps {
+        match step {
+            Step::Pass {name, pass} => {
+                changed |= pass.run(func);
+                if options.print_after {
+                    eprintln!("# after {name}\n{func}");
+                }
+                if options.verify_each {
+                    let diagnostics = verify_function_against(func, signatures);
+                    if !diagnostics.is_empty() {
+                        let lines : Vec<String> = diagnostics.iter().map(|d| format!("after {name}: {d}")).collect();
+                        return Err(lines.join("\n"));
+                    }
+                }
+            },
+            Step::Fixpoint(group) => {
+                let mut iterations = 0;
+                while run_steps(group, func, signatures, options)? {
+                    changed = true;
+                    iterations += 1;
+                    if iterations == MAX_FIXPOINT_ITERATIONS {
+                        eprintln!("warning: @{}: fixpoint group did not converge after {iterations} iterations", func.name);
+                        break;
+                    }
+                }
+            },
+        }
+    }
+    Ok(changed)
+}
+
+/// A registry with the passes of this crate.
+pub fn default_registry() -> Registry {
+    let mut registry = Registry::new();
+    registry.register(PassInfo {
+        name : "tdce",
+        description : "trivial dead code elimination",
+        options : &[],
+        make : |_| Ok(Box::new(crate::tdce::trivial_dce)),
+    });
+    registry.register(PassInfo {
+        name : "adce",
+        description : "aggressive dead code elimination",
+        options : &[],
+        make : |_| Ok(Box::new(crate::adce::adce)),
+    });
+    registry.register(PassInfo {
+        name : "const-prop",
+        description : "global constant propagation",
+        options : &[],
+        make : |_| Ok(Box::new(crate::const_prop::const_prop)),
+    });
+    registry.register(PassInfo {
+        name : "simplify-cfg",
+        description : "remove empty blocks, thread jumps and merge straight-line blocks",
+        options : &[],
+        make : |_| Ok(Box::new(crate::simplify_cfg::simplify_cfg)),
+    });
+    registry.register(PassInfo {
+        name : "licm",
+        description : "loop-invariant code motion",
+        options : &[],
+        make : |_| Ok(Box::new(crate::licm::licm)),
+    });
+    registry.register(PassInfo {
+        name : "pre",
+        description : "partial redundancy elimination",
+        options : &[],
+        make : |_| Ok(Box::new(crate::pre::pre)),
+    });
+    registry.register(PassInfo {
+        name : "lvn",
+        description : "local value numbering",
+        options : &["prop", "comm", "fold", "algebra", "strength", "add-zero", "sub-zero", "sub-self",
+            "mul-zero", "mul-one", "div-one", "cmp-self", "logic-self", "logic-const", "not-not"],
+        make : |o| {
+            let (prop, comm, fold, strength) = (o.flag("prop"), o.flag("comm"), o.flag("fold"), o.flag("strength"));
+            // `algebra` turns on every identity; they can also be chosen one by one.
+            let algebra : HashSet<Identity> = Identity::ALL.into_iter()
+                .filter(|i| o.flag("algebra") || o.flag(i.name())).collect();
+            Ok(Box::new(compare_instrs(move |f : &mut AbstractFunction|
+                local_value_numbering(f, prop, comm, fold, &algebra, strength))))
+        },
+    });
+    registry.register(PassInfo {
+        name : "gvn",
+        description : "dominator-based global value numbering",
+        options : &["prop", "comm"],
+        make : |o| {
+            let (prop, comm) = (o.flag("prop"), o.flag("comm"));
+            Ok(Box::new(compare_instrs(move |f : &mut AbstractFunction| global_value_numbering(f, prop, comm))))
+        },
+    });
+    registry.register(PassInfo {
+        name : "ssa",
+        description : "convert to SSA; mode is minimal, semi-pruned or pruned (the default)",
+        options : &["mode"],
+        make : |o| {
+            let mode = o.value("mode").unwrap_or("pruned").parse::<SsaMode>()?;
+            Ok(Box::new(compare_instrs(move |f : &mut AbstractFunction| to_ssa_mode(f, mode))))
+        },
+    });
+    registry.register(PassInfo {
+        name : "from-ssa",
+        description : "convert out of SSA",
+        options : &[],
+        make : |_| Ok(Box::new(compare_instrs(from_ssa))),
+    });
+    registry.register(PassInfo {
+        name : "sccp",
+        description : "sparse conditional constant propagation, on SSA",
+        options : &[],
+        make : |_| Ok(Box::new(crate::sccp::sccp)),
+    });
+    registry
+}
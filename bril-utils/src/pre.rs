@@ -0,0 +1,443 @@
+use bril_rs::{AbstractFunction, AbstractCode, AbstractInstruction, AbstractType};
+
+use crate::cfg::{Cfg, form_cfg, fresh, reassemble};
+use crate::df::{df_analysis, Dataflow, Direction, Confluence, LiveVars, ProgramPoint};
+use crate::form_blocks::{Block, form_blocks, get_dest, is_terminator};
+use std::collections::{HashMap, HashSet};
+
+/// A computation `op args` that lazy code motion can move. Two instructions
+/// compute the same expression when they apply the same operation to the same
+/// variables.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Expr {
+    pub op : String,
+    pub args : Vec<String>,
+}
+
+impl Expr {
+    pub fn uses(&self, var : &String) -> bool {
+        self.args.contains(var)
+    }
+}
+
+/// Pure operations. Lazy code motion never evaluates an expression on a path
+/// that did not already evaluate it, so `div` is safe to move as well.
+fn is_movable_op(op : &str) -> bool {
+    matches!(op, "add" | "mul" | "sub" | "div" | "eq" | "lt" | "gt" | "le" | "ge"
+        | "not" | "and" | "or" | "fadd" | "fmul" | "fsub" | "fdiv"
+        | "feq" | "flt" | "fgt" | "fle" | "fge" | "ptradd")
+}
+
+pub fn get_expr(instr : &AbstractInstruction) -> Option<Expr> {
+    match instr {
+        AbstractInstruction::Value {op, args, ..} if is_movable_op(op) =>
+            Some(Expr {op : op.to_string(), args : args.clone()}),
+        _ => None,
+    }
+}
+
+fn kill(set : &mut HashSet<Expr>, instr : &AbstractInstruction) {
+    if let Some(dest) = get_dest(instr) {
+        set.retain(|e| !e.uses(dest));
+    }
+}
+
+fn all_exprs(cfg : &Cfg) -> HashSet<Expr> {
+    let mut exprs = HashSet::new();
+    for block in cfg.block_map.values() {
+        for code in &block.instrs {
+            if let AbstractCode::Instruction(instr) = code {
+                exprs.extend(get_expr(instr));
+            }
+        }
+    }
+    exprs
+}
+
+/// Expressions computed on every path from each point before any of their
+/// arguments change.
+pub struct Anticipated {
+    exprs : HashSet<Expr>,
+}
+
+impl Dataflow for Anticipated {
+    type Domain = HashSet<Expr>;
+
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+
+    fn confluence(&self) -> Confluence {
+        Confluence::Meet
+    }
+
+    fn top(&self, _cfg : &Cfg) -> Self::Domain {
+        self.exprs.clone()
+    }
+
+    fn bottom(&self, _cfg : &Cfg) -> Self::Domain {
+        HashSet::new()
+    }
+
+    fn boundary(&self, cfg : &Cfg) -> Self::Domain {
+        self.bottom(cfg)
+    }
+
+    fn transfer(&self, _point : ProgramPoint, instr : &AbstractInstruction,
+        out_b : &Self::Domain) -> Self::Domain {
+        let mut set = out_b.clone();
+        kill(&mut set, instr);
+        set.extend(get_expr(instr));
+        set
+    }
+}
+
+/// Expressions computed on every path to each point with none of their
+/// arguments changed since.
+pub struct Available {
+    exprs : HashSet<Expr>,
+}
+
+impl Dataflow for Available {
+    type Domain = HashSet<Expr>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn confluence(&self) -> Confluence {
+        Confluence::Meet
+    }
+
+    fn top(&self, _cfg : &Cfg) -> Self::Domain {
+        self.exprs.clone()
+    }
+
+    fn bottom(&self, _cfg : &Cfg) -> Self::Domain {
+        HashSet::new()
+    }
+
+    fn boundary(&self, cfg : &Cfg) -> Self::Domain {
+        self.bottom(cfg)
+    }
+
+    fn transfer(&self, _point : ProgramPoint, instr : &AbstractInstruction,
+        in_b : &Self::Domain) -> Self::Domain {
+        let mut set = in_b.clone();
+        set.extend(get_expr(instr));
+        kill(&mut set, instr);
+        set
+    }
+}
+
+/// Expressions whose computation can be postponed to each point: placing them
+/// earliest is safe, and no path since then has used them. The value at the
+/// start of a block is restricted to the expressions anticipated there, and
+/// `earliest` holds, for each block, the expressions that are earliest on
+/// every edge leaving it.
+pub struct Later {
+    exprs : HashSet<Expr>,
+    ant_in : HashMap<i32, HashSet<Expr>>,
+    earliest : HashMap<i32, HashSet<Expr>>,
+}
+
+impl Dataflow for Later {
+    type Domain = HashSet<Expr>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn confluence(&self) -> Confluence {
+        Confluence::Meet
+    }
+
+    fn top(&self, _cfg : &Cfg) -> Self::Domain {
+        self.exprs.clone()
+    }
+
+    fn bottom(&self, _cfg : &Cfg) -> Self::Domain {
+        HashSet::new()
+    }
+
+    fn transfer(&self, point : ProgramPoint, instr : &AbstractInstruction,
+        in_b : &Self::Domain) -> Self::Domain {
+        let mut set = if point.index == 0 {
+            in_b.intersection(self.ant_in.get(&point.block).unwrap()).cloned().collect()
+        } else {
+            in_b.clone()
+        };
+        if let Some(e) = get_expr(instr) {
+            set.remove(&e);
+        }
+        if is_terminator(instr) {
+            set.extend(self.earliest.get(&point.block).unwrap().iter().cloned());
+        }
+        set
+    }
+}
+
+/// Expressions computed in `block` before any of their arguments change.
+fn upward_exposed(block : &Block) -> HashSet<Expr> {
+    let mut exposed = HashSet::new();
+    let mut killed : HashSet<&String> = HashSet::new();
+    for code in &block.instrs {
+        if let AbstractCode::Instruction(instr) = code {
+            if let Some(e) = get_expr(instr) {
+                if e.args.iter().all(|a| !killed.contains(a)) {
+                    exposed.insert(e);
+                }
+            }
+            killed.extend(get_dest(instr));
+        }
+    }
+    exposed
+}
+
+fn transparent(block : &Block, e : &Expr) -> bool {
+    block.instrs.iter().all(|code| match code {
+        AbstractCode::Instruction(instr) => !get_dest(instr).map(|d| e.uses(d)).unwrap_or(false),
+        AbstractCode::Label {..} => true,
+    })
+}
+
+/// Placement computed by lazy code motion: `insert` maps edges to the
+/// expressions to evaluate along them and `delete` maps blocks to the
+/// expressions whose first computation becomes redundant.
+struct Placement {
+    insert : Vec<((i32, i32), Vec<Expr>)>,
+    delete : HashMap<i32, HashSet<Expr>>,
+}
+
+fn lazy_code_motion(cfg : &Cfg) -> Placement {
+    let exprs = all_exprs(cfg);
+    let ant = df_analysis(cfg, &Anticipated {exprs : exprs.clone()});
+    let avail = df_analysis(cfg, &Available {exprs : exprs.clone()});
+
+    // An expression is earliest on an edge `i -> j` if it is anticipated at
+    // `j`, not available at the end of `i`, and could not be computed any
+    // earlier in `i`; the last part only depends on `i`.
+    let mut earliest = HashMap::new();
+    for (num, block) in &cfg.block_map {
+        let set : HashSet<Expr> = exprs.iter().filter(|e| {
+            !avail.block_out(*num).contains(e)
+                && (!transparent(block, e) || !ant.block_out(*num).contains(e))
+        }).cloned().collect();
+        earliest.insert(*num, set);
+    }
+
+    let later_df = Later {exprs : exprs.clone(), ant_in : ant.in_map.clone(), earliest};
+    let later = df_analysis(cfg, &later_df);
+    let later_in = |num : i32| -> HashSet<Expr> {
+        later.block_in(num).intersection(ant.block_in(num)).cloned().collect()
+    };
+
+    let mut blocks : Vec<i32> = cfg.block_map.keys().copied().collect();
+    blocks.sort();
+    let mut insert = vec![];
+    let mut delete = HashMap::new();
+    for i in &blocks {
+        let mut succ = cfg.succ.get(i).unwrap().clone();
+        succ.sort();
+        succ.dedup();
+        for j in &succ {
+            let later_ij : HashSet<Expr> = later.block_out(*i)
+                .intersection(ant.block_in(*j)).cloned().collect();
+            let mut exprs : Vec<Expr> = later_ij.difference(&later_in(*j)).cloned().collect();
+            if !exprs.is_empty() {
+                exprs.sort();
+                insert.push(((*i, *j), exprs));
+            }
+        }
+        let block = cfg.block_map.get(i).unwrap();
+        let del : HashSet<Expr> = upward_exposed(block).difference(&later_in(*i)).cloned().collect();
+        delete.insert(*i, del);
+    }
+    Placement {insert, delete}
+}
+
+fn compute(e : &Expr, dest : &str, op_type : &Option<AbstractType>) -> AbstractCode {
+    AbstractCode::Instruction(AbstractInstruction::Value {
+        op : e.op.to_string(),
+        args : e.args.clone(),
+        dest : dest.to_string(),
+        op_type : op_type.clone(),
+        funcs : vec![],
+        labels : vec![],
+    })
+}
+
+fn copy(src : &str, dest : &str, op_type : &Option<AbstractType>) -> AbstractCode {
+    AbstractCode::Instruction(AbstractInstruction::Value {
+        op : "id".to_string(),
+        args : vec![src.to_string()],
+        dest : dest.to_string(),
+        op_type : op_type.clone(),
+        funcs : vec![],
+        labels : vec![],
+    })
+}
+
+/// Rewrites the computations of `block`: the first computation of each
+/// deleted expression reads its temporary instead, and the last computation
+/// of every expression that survives to the end of the block also saves its
+/// value in the temporary.
+fn rewrite_block(block : &mut Block, delete : &HashSet<Expr>,
+    temps : &HashMap<Expr, (String, Option<AbstractType>)>) {
+    let mut exposed : HashSet<Expr> = HashSet::new();
+    let mut killed : HashSet<String> = HashSet::new();
+    let mut last_comp : HashMap<Expr, usize> = HashMap::new();
+    for (i, code) in block.instrs.iter().enumerate() {
+        if let AbstractCode::Instruction(instr) = code {
+            if let Some(e) = get_expr(instr) {
+                last_comp.insert(e, i);
+            }
+            if let Some(dest) = get_dest(instr) {
+                last_comp.retain(|e, _| !e.uses(dest));
+            }
+        }
+    }
+
+    let mut instrs = vec![];
+    for (i, code) in block.instrs.drain(..).enumerate() {
+        let (e, dest) = match &code {
+            AbstractCode::Instruction(instr) => match (get_expr(instr), get_dest(instr)) {
+                (Some(e), Some(dest)) => (e, dest.to_string()),
+                _ => {
+                    killed.extend(get_dest(instr).cloned());
+                    instrs.push(code);
+                    continue;
+                },
+            },
+            AbstractCode::Label {..} => {
+                instrs.push(code);
+                continue;
+            },
+        };
+        let (temp, op_type) = temps.get(&e).unwrap();
+        let first = e.args.iter().all(|a| !killed.contains(a)) && exposed.insert(e.clone());
+        if first && delete.contains(&e) {
+            instrs.push(copy(temp, &dest, op_type));
+        } else if last_comp.get(&e) == Some(&i) {
+            instrs.push(compute(&e, temp, op_type));
+            instrs.push(copy(temp, &dest, op_type));
+        } else {
+            instrs.push(code);
+        }
+        killed.insert(dest);
+    }
+    block.instrs = instrs;
+}
+
+/// Turns `t = e; x = id t` back into `x = e` when nothing else reads `t`.
+fn remove_dead_temps(cfg : &mut Cfg, temps : &HashSet<String>) {
+    let live = df_analysis(cfg, &LiveVars);
+    for (num, block) in cfg.block_map.iter_mut() {
+        let point = ProgramPoint::new(*num, 0);
+        // Built backwards: the last element is the instruction that follows
+        // the one being visited, and `live_after_next` is live after it.
+        let mut instrs : Vec<AbstractCode> = vec![];
+        let mut live_vars = live.block_out(*num).clone();
+        let mut live_after_next = live_vars.clone();
+        while let Some(code) = block.instrs.pop() {
+            let mut instr = match code {
+                AbstractCode::Instruction(instr) => instr,
+                AbstractCode::Label {..} => {
+                    instrs.push(code);
+                    continue;
+                },
+            };
+            let copy_dest = match (&instr, instrs.last()) {
+                (AbstractInstruction::Value {dest : t, ..},
+                    Some(AbstractCode::Instruction(AbstractInstruction::Value {op, args, dest, ..})))
+                    if temps.contains(t) && op == "id" && args[0] == *t
+                        && !live_after_next.contains(t) => Some(dest.to_string()),
+                _ => None,
+            };
+            match copy_dest {
+                Some(x) => {
+                    instrs.pop();
+                    if let AbstractInstruction::Value {dest, ..} = &mut instr {
+                        *dest = x;
+                    }
+                },
+                None => live_after_next = live_vars.clone(),
+            }
+            live_vars = LiveVars.transfer(point, &instr, &live_after_next);
+            instrs.push(AbstractCode::Instruction(instr));
+        }
+        instrs.reverse();
+        block.instrs = instrs;
+    }
+}
+
+/// Partial redundancy elimination by lazy code motion (Knoop, Rüthing and
+/// Steffen, in the edge-based form of Drechsler and Stadel). Each expression
+/// is evaluated into a fresh temporary at the latest points where it is
+/// available on every path to its uses, critical edges are split when such a
+/// point lies on one, and computations that become fully redundant read the
+/// temporary instead. Works on code that is not in SSA form; functions with
+/// phis are left unchanged. Returns whether the function changed.
+pub fn pre(func : &mut AbstractFunction) -> bool {
+    let has_phi = func.instrs.iter().any(|code| matches!(code,
+        AbstractCode::Instruction(AbstractInstruction::Value {op, ..}) if op == "phi"));
+    if has_phi {
+        return false;
+    }
+    let mut cfg = form_cfg(form_blocks(func));
+    let placement = lazy_code_motion(&cfg);
+    let changed = !placement.insert.is_empty()
+        || placement.delete.values().any(|d| !d.is_empty());
+    if !changed {
+        return false;
+    }
+
+    let mut vars : HashSet<String> = func.args.iter().map(|a| a.name.clone()).collect();
+    let mut types : HashMap<Expr, Option<AbstractType>> = HashMap::new();
+    for block in cfg.block_map.values() {
+        for code in &block.instrs {
+            if let AbstractCode::Instruction(instr) = code {
+                vars.extend(get_dest(instr).cloned());
+                if let (Some(e), AbstractInstruction::Value {op_type, ..}) = (get_expr(instr), instr) {
+                    types.insert(e, op_type.clone());
+                }
+            }
+        }
+    }
+    let mut exprs : Vec<Expr> = types.keys().cloned().collect();
+    exprs.sort();
+    let mut temps = HashMap::new();
+    for e in exprs {
+        let temp = fresh(format!("{}.pre", e.op), vars.iter());
+        vars.insert(temp.to_string());
+        let op_type = types.get(&e).unwrap().clone();
+        temps.insert(e, (temp, op_type));
+    }
+
+    for (num, block) in cfg.block_map.iter_mut() {
+        rewrite_block(block, placement.delete.get(num).unwrap(), &temps);
+    }
+
+    for ((from, to), exprs) in placement.insert {
+        let code : Vec<AbstractCode> = exprs.iter().map(|e| {
+            let (temp, op_type) = temps.get(e).unwrap();
+            compute(e, temp, op_type)
+        }).collect();
+        if cfg.succ.get(&from).unwrap().len() == 1 {
+            let block = cfg.block_map.get_mut(&from).unwrap();
+            let at = block.instrs.len() - 1;
+            block.instrs.splice(at..at, code);
+        } else if cfg.pred.get(&to).unwrap().len() == 1 {
+            let block = cfg.block_map.get_mut(&to).unwrap();
+            block.instrs.splice(0..0, code);
+        } else {
+            let num = cfg.split_edge(from, to);
+            cfg.block_map.get_mut(&num).unwrap().instrs.splice(0..0, code);
+        }
+    }
+
+    let temp_names = temps.into_values().map(|(t, _)| t).collect();
+    remove_dead_temps(&mut cfg, &temp_names);
+    func.instrs = reassemble(cfg);
+    true
+}
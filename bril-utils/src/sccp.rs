@@ -0,0 +1,294 @@
+use std::collections::{HashMap, HashSet};
+use bril_rs::{AbstractFunction, AbstractCode, AbstractInstruction, ConstOps, Literal};
+use crate::cfg::*;
+use crate::const_prop::{ConstValue, eval_value, is_foldable};
+use crate::df::{Lattice, ProgramPoint};
+use crate::form_blocks::*;
+
+/// Sparse conditional constant propagation state for a function in SSA form.
+/// Edges are `(pred, succ)` block numbers; the entry block is reached along
+/// an edge from `None`.
+struct Sccp<'a> {
+    cfg : &'a Cfg,
+    values : HashMap<String, ConstValue>,
+    exec_edges : HashSet<(i32, i32)>,
+    exec_blocks : HashSet<i32>,
+    uses : HashMap<String, Vec<ProgramPoint>>,
+    flow_worklist : Vec<(Option<i32>, i32)>,
+    ssa_worklist : Vec<ProgramPoint>,
+}
+
+fn get_instr(cfg : &Cfg, point : ProgramPoint) -> Option<&AbstractInstruction> {
+    let block = cfg.block_map.get(&point.block).unwrap();
+    match block.instrs.get(point.index) {
+        Some(AbstractCode::Instruction(instr)) => Some(instr),
+        _ => None,
+    }
+}
+
+fn is_phi(instr : &AbstractInstruction) -> bool {
+    matches!(instr, AbstractInstruction::Value {op, ..} if op == "phi")
+}
+
+impl<'a> Sccp<'a> {
+    fn new(cfg : &'a Cfg, args : &[String]) -> Self {
+        let mut uses : HashMap<String, Vec<ProgramPoint>> = HashMap::new();
+        for (num, block) in &cfg.block_map {
+            for (i, code) in block.instrs.iter().enumerate() {
+                if let AbstractCode::Instruction(instr) = code {
+                    for a in get_args(instr) {
+                        uses.entry(a.to_string()).or_default().push(ProgramPoint::new(*num, i));
+                    }
+                }
+            }
+        }
+        let values = args.iter().map(|a| (a.to_string(), ConstValue::Overdefined)).collect();
+        let (&entry, _) = cfg.block_map.first().unwrap();
+        Sccp {
+            cfg,
+            values,
+            exec_edges : HashSet::new(),
+            exec_blocks : HashSet::new(),
+            uses,
+            flow_worklist : vec![(None, entry)],
+            ssa_worklist : vec![],
+        }
+    }
+
+    fn lookup(&self, var : &String) -> ConstValue {
+        self.values.get(var).cloned().unwrap_or(ConstValue::Undefined)
+    }
+
+    fn block_num(&self, label : &String) -> i32 {
+        *self.cfg.name_map.get_by_right(label).unwrap()
+    }
+
+    fn update(&mut self, dest : &String, val : ConstValue) {
+        let old = self.lookup(dest);
+        let new = old.join(&val);
+        if new != old {
+            self.values.insert(dest.to_string(), new);
+            if let Some(uses) = self.uses.get(dest) {
+                self.ssa_worklist.extend(uses);
+            }
+        }
+    }
+
+    fn add_edge(&mut self, from : i32, label : &String) {
+        let to = self.block_num(label);
+        if self.exec_edges.insert((from, to)) {
+            self.flow_worklist.push((Some(from), to));
+        }
+    }
+
+    fn visit(&mut self, point : ProgramPoint) {
+        let instr = match get_instr(self.cfg, point) {
+            Some(instr) => instr,
+            None => return,
+        };
+        match instr {
+            AbstractInstruction::Constant {dest, value, ..} => {
+                self.update(dest, ConstValue::Const(value.clone()));
+            },
+            AbstractInstruction::Value {op, dest, args, labels, ..} if op == "phi" => {
+                let mut val = ConstValue::Undefined;
+                for (a, l) in args.iter().zip(labels) {
+                    if self.exec_edges.contains(&(self.block_num(l), point.block)) {
+                        val = val.join(&self.lookup(a));
+                    }
+                }
+                self.update(dest, val);
+            },
+            AbstractInstruction::Value {op, dest, args, ..} => {
+                let val = if is_foldable(op) {
+                    let vals : Vec<ConstValue> = args.iter().map(|a| self.lookup(a)).collect();
+                    eval_value(op, &vals)
+                } else {
+                    ConstValue::Overdefined
+                };
+                self.update(dest, val);
+            },
+            AbstractInstruction::Effect {op, args, labels, ..} => {
+                match op.as_str() {
+                    "jmp" => self.add_edge(point.block, &labels[0]),
+                    "br" => match self.lookup(&args[0]) {
+                        ConstValue::Const(Literal::Bool(true)) => self.add_edge(point.block, &labels[0]),
+                        ConstValue::Const(Literal::Bool(false)) => self.add_edge(point.block, &labels[1]),
+                        ConstValue::Undefined => (),
+                        _ => {
+                            self.add_edge(point.block, &labels[0]);
+                            self.add_edge(point.block, &labels[1]);
+                        },
+                    },
+                    _ => (),
+                }
+            },
+        }
+    }
+
+    fn visit_block(&mut self, num : i32, phis_only : bool) {
+        let len = self.cfg.block_map.get(&num).unwrap().len();
+        for i in 0..len {
+            let point = ProgramPoint::new(num, i);
+            match get_instr(self.cfg, point) {
+                Some(instr) if phis_only && !is_phi(instr) => break,
+                _ => self.visit(point),
+            }
+        }
+    }
+
+    fn propagate(&mut self) {
+        loop {
+            if let Some((_, to)) = self.flow_worklist.pop() {
+                let first_visit = self.exec_blocks.insert(to);
+                self.visit_block(to, !first_visit);
+            } else if let Some(point) = self.ssa_worklist.pop() {
+                if self.exec_blocks.contains(&point.block) {
+                    self.visit(point);
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Branches whose condition is still undefined at the fixed point only
+    /// read undefined variables; follow both targets so that the output
+    /// stays well formed.
+    fn force_undefined_branches(&mut self) -> bool {
+        let mut forced = false;
+        let mut blocks : Vec<i32> = self.exec_blocks.iter().copied().collect();
+        blocks.sort();
+        for num in blocks {
+            let block = self.cfg.block_map.get(&num).unwrap();
+            if let Some(AbstractCode::Instruction(AbstractInstruction::Effect {op, args, labels, ..}))
+                = block.instrs.last() {
+                if op == "br" && self.lookup(&args[0]) == ConstValue::Undefined {
+                    let before = self.exec_edges.len();
+                    self.add_edge(num, &labels[0]);
+                    self.add_edge(num, &labels[1]);
+                    forced |= self.exec_edges.len() != before;
+                }
+            }
+        }
+        forced
+    }
+
+    fn solve(&mut self) {
+        self.propagate();
+        while self.force_undefined_branches() {
+            self.propagate();
+        }
+    }
+}
+
+fn rewrite_block(sccp : &Sccp, num : i32, instrs : &mut [AbstractCode]) -> bool {
+    let cfg = sccp.cfg;
+    let mut changed = false;
+    for code in instrs.iter_mut() {
+        let instr = match code {
+            AbstractCode::Instruction(instr) => instr,
+            AbstractCode::Label {..} => continue,
+        };
+        let new_instr = match instr {
+            AbstractInstruction::Value {op, dest, op_type, ..}
+                if is_foldable(op) && matches!(sccp.lookup(dest), ConstValue::Const(_)) => {
+                match sccp.lookup(dest) {
+                    ConstValue::Const(value) => Some(AbstractInstruction::Constant {
+                        dest : dest.to_string(),
+                        op : ConstOps::Const,
+                        const_type : op_type.clone(),
+                        value,
+                    }),
+                    _ => None,
+                }
+            },
+            AbstractInstruction::Value {op, dest, op_type, args, labels, ..} if op == "phi" => {
+                let live : Vec<(String, String)> = args.iter().zip(labels.iter())
+                    .filter(|(_, l)| {
+                        let pred = *cfg.name_map.get_by_right(*l).unwrap();
+                        sccp.exec_edges.contains(&(pred, num))
+                    })
+                    .map(|(a, l)| (a.to_string(), l.to_string()))
+                    .collect();
+                let single = live.iter().all(|(a, _)| *a == live[0].0);
+                if !live.is_empty() && single && live[0].0 != "__undefined" {
+                    Some(AbstractInstruction::Value {
+                        op : "id".to_string(),
+                        dest : dest.to_string(),
+                        op_type : op_type.clone(),
+                        args : vec![live[0].0.to_string()],
+                        labels : vec![],
+                        funcs : vec![],
+                    })
+                } else if live.len() != args.len() {
+                    let (new_args, new_labels) = live.into_iter().unzip();
+                    *args = new_args;
+                    *labels = new_labels;
+                    changed = true;
+                    None
+                } else {
+                    None
+                }
+            },
+            AbstractInstruction::Effect {op, labels, ..} if op == "br" => {
+                let targets : Vec<&String> = labels.iter().filter(|l| {
+                    let to = *cfg.name_map.get_by_right(*l).unwrap();
+                    sccp.exec_edges.contains(&(num, to))
+                }).collect();
+                if targets.len() == 1 || labels[0] == labels[1] {
+                    Some(AbstractInstruction::Effect {
+                        op : "jmp".to_string(),
+                        args : vec![],
+                        funcs : vec![],
+                        labels : vec![targets[0].to_string()],
+                    })
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        };
+        if let Some(new_instr) = new_instr {
+            *instr = new_instr;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Sparse conditional constant propagation on a function in SSA form, such as
+/// the output of `to_ssa`. Folds constants, removes unreachable blocks and
+/// simplifies phis left with a single reachable incoming value. Returns
+/// whether the function changed.
+pub fn sccp(func : &mut AbstractFunction) -> bool {
+    let mut cfg = form_cfg(form_blocks(func));
+    let args : Vec<String> = func.args.iter().map(|a| a.name.clone()).collect();
+
+    let mut sccp = Sccp::new(&cfg, &args);
+    sccp.solve();
+
+    let mut new_blocks = vec![];
+    let mut changed = false;
+    for (num, block) in &cfg.block_map {
+        if sccp.exec_blocks.contains(num) {
+            let mut instrs = block.instrs.clone();
+            changed |= rewrite_block(&sccp, *num, &mut instrs);
+            new_blocks.push((*num, instrs));
+        } else {
+            changed = true;
+        }
+    }
+
+    let dead : Vec<i32> = cfg.block_map.keys().copied()
+        .filter(|num| !sccp.exec_blocks.contains(num)).collect();
+    for (num, instrs) in new_blocks {
+        cfg.block_map.get_mut(&num).unwrap().instrs = instrs;
+    }
+    for num in dead {
+        cfg.remove_block(num);
+    }
+
+    func.instrs = reassemble(cfg);
+    changed
+}
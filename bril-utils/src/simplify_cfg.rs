@@ -0,0 +1,139 @@
+use bril_rs::{AbstractFunction, AbstractCode, AbstractInstruction};
+
+use crate::cfg::{Cfg, form_cfg, reassemble};
+use crate::form_blocks::form_blocks;
+
+fn jmp(label : &str) -> AbstractCode {
+    AbstractCode::Instruction(AbstractInstruction::Effect {
+        op : "jmp".to_string(),
+        args : vec![],
+        funcs : vec![],
+        labels : vec![label.to_string()],
+    })
+}
+
+fn has_phis(cfg : &Cfg, block : i32) -> bool {
+    cfg.block_map.get(&block).unwrap().instrs.iter().any(|code| matches!(code,
+        AbstractCode::Instruction(AbstractInstruction::Value {op, ..}) if op == "phi"))
+}
+
+/// Turns `br c .l .l` into `jmp .l`.
+fn fold_branches(cfg : &mut Cfg) -> bool {
+    let mut changed = false;
+    let nums : Vec<i32> = cfg.block_map.keys().copied().collect();
+    for num in nums {
+        let target = match cfg.block_map.get(&num).unwrap().instrs.last() {
+            Some(AbstractCode::Instruction(AbstractInstruction::Effect {op, labels, ..}))
+                if op == "br" && labels[0] == labels[1] => labels[0].to_string(),
+            _ => continue,
+        };
+        cfg.replace_terminator(num, jmp(&target));
+        changed = true;
+    }
+    changed
+}
+
+/// The target of `block` if it does nothing but jump there.
+fn jump_target(cfg : &Cfg, block : i32) -> Option<i32> {
+    match cfg.block_map.get(&block).unwrap().instrs.as_slice() {
+        [AbstractCode::Instruction(AbstractInstruction::Effect {op, labels, ..})] if op == "jmp" => {
+            let target = cfg.num(&labels[0]);
+            if target != block { Some(target) } else { None }
+        },
+        _ => None,
+    }
+}
+
+/// Makes the predecessors of an empty block jump straight to its target.
+/// The phis of the target get one operand per predecessor in place of the
+/// one from the empty block, so this is skipped when a predecessor already
+/// reaches the target along another edge.
+fn thread_jump(cfg : &mut Cfg, block : i32) -> bool {
+    let target = match jump_target(cfg, block) {
+        Some(target) if cfg.entry() != Some(block) => target,
+        _ => return false,
+    };
+    let mut preds = cfg.pred.get(&block).unwrap().clone();
+    preds.sort();
+    preds.dedup();
+    if preds.is_empty() {
+        return false;
+    }
+    if has_phis(cfg, target) {
+        let target_preds = cfg.pred.get(&target).unwrap();
+        if preds.iter().any(|p| target_preds.contains(p)) {
+            return false;
+        }
+        let label = cfg.name(block).to_string();
+        let pred_labels : Vec<String> = preds.iter().map(|p| cfg.name(*p).to_string()).collect();
+        for code in cfg.block_map.get_mut(&target).unwrap().instrs.iter_mut() {
+            if let AbstractCode::Instruction(AbstractInstruction::Value {op, args, labels, ..}) = code {
+                if op != "phi" {
+                    continue;
+                }
+                if let Some(i) = labels.iter().position(|l| *l == label) {
+                    let arg = args.remove(i);
+                    labels.remove(i);
+                    for l in &pred_labels {
+                        args.push(arg.to_string());
+                        labels.push(l.to_string());
+                    }
+                }
+            }
+        }
+    }
+    for p in preds {
+        cfg.redirect_edge(p, block, target);
+    }
+    cfg.remove_block(block);
+    true
+}
+
+/// Merges every block into its predecessor when each is the only neighbour
+/// of the other.
+fn merge_straight_line(cfg : &mut Cfg) -> bool {
+    let mut changed = false;
+    let nums : Vec<i32> = cfg.block_map.keys().copied().collect();
+    for a in nums {
+        while cfg.block_map.contains_key(&a) {
+            let succ = cfg.succ.get(&a).unwrap();
+            match succ.first().copied() {
+                Some(b) if succ.iter().all(|s| *s == b) && cfg.merge_blocks(a, b) => changed = true,
+                _ => break,
+            }
+        }
+    }
+    changed
+}
+
+/// Simplifies the control flow of `cfg` until nothing changes: removes
+/// unreachable blocks, folds branches whose targets are the same, threads
+/// jumps through empty blocks and merges straight-line blocks. Returns
+/// whether anything changed.
+pub fn simplify(cfg : &mut Cfg) -> bool {
+    let mut changed = false;
+    loop {
+        let mut progress = cfg.remove_unreachable();
+        progress |= fold_branches(cfg);
+        let nums : Vec<i32> = cfg.block_map.keys().copied().collect();
+        for num in nums {
+            if cfg.block_map.contains_key(&num) {
+                progress |= thread_jump(cfg, num);
+            }
+        }
+        progress |= merge_straight_line(cfg);
+        if !progress {
+            return changed;
+        }
+        changed = true;
+    }
+}
+
+/// Cleans up the trivial blocks and jumps left behind by forming the CFG
+/// and by other passes. See `simplify`.
+pub fn simplify_cfg(func : &mut AbstractFunction) -> bool {
+    let mut cfg = form_cfg(form_blocks(func));
+    let changed = simplify(&mut cfg);
+    func.instrs = reassemble(cfg);
+    changed
+}
@@ -0,0 +1,470 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use bril_rs::AbstractCode;
+use bril_rs::AbstractInstruction;
+use bril_rs::AbstractType;
+use crate::cfg::*;
+use crate::dominators::*;
+use crate::form_blocks::*;
+use bril_rs::AbstractFunction;
+use crate::tdce::*;
+use crate::df::{df_analysis, PhiLiveVars};
+
+pub mod verify;
+
+type Defs = HashMap<String, HashMap<i32, Option<AbstractType>>>;
+
+fn get_def_blocks(cfg : &Cfg) -> Defs {
+    let mut defs = HashMap::new();
+    for (block_num, block) in &cfg.block_map {
+        for instr in &block.instrs {
+            if let AbstractCode::Instruction(AbstractInstruction::Value {dest, op_type, ..}
+                                             | AbstractInstruction::Constant {dest, const_type: op_type, ..}) = instr {
+                let op_type = op_type.clone().unwrap();
+                if !defs.contains_key(dest) {
+                    defs.insert(dest.to_string(), HashMap::new());
+                }
+                let blocks = defs.get_mut(dest).unwrap();
+                blocks.insert(*block_num, Some(op_type));
+            }
+        }
+    }
+
+    defs
+}
+
+#[derive(Eq, Debug)]
+struct Phi {
+    dest : String,
+    orig_dest : String,
+    vars : Vec<String>,
+    labels : Vec<i32>,
+    op_type : Option<AbstractType>,
+}
+
+impl Phi {
+    fn new(dest : String, op_type : Option<AbstractType>) -> Self {
+        Phi {dest : dest.clone(), orig_dest : dest, vars : vec![], labels : vec![], op_type}
+    }
+}
+
+impl PartialEq for Phi {
+    fn eq(&self, other: &Self) -> bool {
+        self.dest == other.dest && self.vars == other.vars && self.labels == other.labels
+    }
+}
+
+/// Which variables get phis at the dominance frontiers of their definitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SsaMode {
+    /// Every variable, at every block of the iterated dominance frontier.
+    Minimal,
+    /// Only variables that are used in some block before being defined
+    /// there, since only those can be live on entry to a block.
+    SemiPruned,
+    /// Only where the variable is live on entry to the block.
+    Pruned,
+}
+
+impl FromStr for SsaMode {
+    type Err = String;
+
+    fn from_str(s : &str) -> Result<Self, Self::Err> {
+        match s {
+            "minimal" => Ok(SsaMode::Minimal),
+            "semi-pruned" => Ok(SsaMode::SemiPruned),
+            "pruned" => Ok(SsaMode::Pruned),
+            _ => Err(format!("unknown mode `{s}`")),
+        }
+    }
+}
+
+/// Variables read in some block before any definition in that block.
+fn global_names(cfg : &Cfg) -> HashSet<String> {
+    let mut globals = HashSet::new();
+    for block in cfg.block_map.values() {
+        let mut killed = HashSet::new();
+        for code in &block.instrs {
+            if let AbstractCode::Instruction(instr) = code {
+                for a in get_args(instr) {
+                    if !killed.contains(a) {
+                        globals.insert(a.to_string());
+                    }
+                }
+                if let Some(dest) = get_dest(instr) {
+                    killed.insert(dest.to_string());
+                }
+            }
+        }
+    }
+    globals
+}
+
+/// Places phis at the iterated dominance frontier of the definitions of each
+/// variable, keeping only the ones `mode` asks for.
+fn get_phi_nodes(cfg : &Cfg, mode : SsaMode) -> HashMap<i32, Vec<Phi>> {
+    let defs = get_def_blocks(cfg);
+    let frontier = get_dominance_frontier_num(cfg);
+    let globals = match mode {
+        SsaMode::SemiPruned => Some(global_names(cfg)),
+        _ => None,
+    };
+    let live = match mode {
+        SsaMode::Pruned => Some(df_analysis(cfg, &PhiLiveVars::new(cfg))),
+        _ => None,
+    };
+
+    let mut vars : Vec<&String> = defs.keys().collect();
+    vars.sort();
+    let mut phi_nodes : HashMap<i32, Vec<Phi>> = HashMap::new();
+    for v in vars {
+        if globals.as_ref().is_some_and(|g| !g.contains(v)) {
+            continue;
+        }
+        let mut worklist : Vec<(i32, Option<AbstractType>)> = defs.get(v).unwrap().iter()
+            .map(|(b, op_type)| (*b, op_type.clone())).collect();
+        worklist.sort_by_key(|(b, _)| *b);
+        let mut visited : HashSet<i32> = worklist.iter().map(|(b, _)| *b).collect();
+        let mut placed = HashSet::new();
+        while let Some((d, op_type)) = worklist.pop() {
+            for block in frontier.get(&d).unwrap() {
+                if placed.contains(block) {
+                    continue;
+                }
+                if live.as_ref().is_some_and(|l| !l.block_in(*block).contains(v)) {
+                    continue;
+                }
+                placed.insert(*block);
+                phi_nodes.entry(*block).or_default().push(Phi::new(v.to_string(), op_type.clone()));
+                // The phi is a new definition of `v`, so its frontier needs
+                // phis as well.
+                if visited.insert(*block) {
+                    worklist.push((*block, op_type.clone()));
+                }
+            }
+        }
+    }
+    phi_nodes
+}
+
+fn insert_phi_nodes(cfg : &mut Cfg, phi_nodes : HashMap<i32, Vec<Phi>>) {
+    for (block, phis) in phi_nodes {
+        for phi in phis.into_iter().rev() {
+            let labels : Vec<_> = phi.labels.iter().map(|l|
+                cfg.name_map.get_by_left(l).unwrap().to_string()).collect();
+
+            let phi = AbstractCode::Instruction(AbstractInstruction::Value {
+                dest: phi.dest.to_string(), args: phi.vars, labels, 
+                op_type: phi.op_type.clone(), funcs: vec![], op: "phi".to_string()});
+            cfg.block_map.get_mut(&block).unwrap().instrs.insert(0, phi);
+        }
+    }
+}
+
+fn fresh_name(curr_name: &String, counters : &mut HashMap<String, i32>) -> String {
+    if !counters.contains_key(curr_name) {
+        counters.insert(curr_name.to_string(), 0);
+    }
+    let i = counters.get_mut(curr_name).unwrap();
+    let new_name = format!("{curr_name}.{i}");
+    *i += 1;
+    new_name
+}
+
+fn rename(cfg : &mut Cfg, block_num : i32, phis : &mut HashMap<i32, Vec<Phi>>,
+    dom_tree : &DomTree, stacks : &mut HashMap<String, Vec<String>>, 
+    counters : &mut HashMap<String, i32>) {
+    let block = cfg.block_map.get_mut(&block_num).unwrap();
+    
+    // Variables pushed in this block, popped again once the subtree is done.
+    let mut pushed = vec![];
+
+    if phis.contains_key(&block_num) {
+        for phi in phis.get_mut(&block_num).unwrap() {
+            let name = fresh_name(&phi.dest, counters);
+            stacks.get_mut(&phi.dest).unwrap().push(name.clone());
+            pushed.push(phi.dest.to_string());
+            phi.dest = name;
+        }
+    }
+
+    for code in &mut block.instrs {
+        if let AbstractCode::Instruction(instr) = code {
+            match instr {
+                // Phis already in the input read their arguments at the end
+                // of the predecessors, so those are renamed from there.
+                AbstractInstruction::Value {op, ..} if op == "phi" => (),
+                AbstractInstruction::Value {args, ..}
+                | AbstractInstruction::Effect {args, ..} => {
+                    for a in args {
+                        let new_name = stacks.get(a).unwrap().last().unwrap();
+                        *a = new_name.to_string();
+                    }           
+                },
+                _ => (),
+            }
+
+            match instr {
+                AbstractInstruction::Value {dest, ..}
+                | AbstractInstruction::Constant {dest, ..} => {
+                    let name = fresh_name(dest, counters);
+                    stacks.get_mut(dest).unwrap().push(name.clone());
+                    pushed.push(dest.to_string());
+                    *dest = name;
+                },
+                _ => (),
+            }
+        }
+    }
+
+    let label = cfg.name_map.get_by_left(&block_num).unwrap().to_string();
+    let mut succ = cfg.succ.get(&block_num).unwrap().clone();
+    succ.sort();
+    succ.dedup();
+    for s in succ {
+        for code in &mut cfg.block_map.get_mut(&s).unwrap().instrs {
+            if let AbstractCode::Instruction(AbstractInstruction::Value {op, args, labels, ..}) = code {
+                if op == "phi" {
+                    for (a, l) in args.iter_mut().zip(labels.iter()) {
+                        if *l == label {
+                            *a = match stacks.get(a).and_then(|stack| stack.last()) {
+                                Some(v) => v.to_string(),
+                                None => "__undefined".to_string(),
+                            };
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(phis) = phis.get_mut(&s) {
+            for p in phis.iter_mut() {
+                let v = stacks.get(&p.orig_dest).unwrap().last();
+                match v {
+                    Some(v) => {
+                        p.vars.push(v.to_string());
+                    },
+                    None => {
+                        p.vars.push("__undefined".to_string())
+                    },
+                }
+                p.labels.push(block_num);
+            }
+        }
+    }
+
+    for b in dom_tree.children(&block_num) {
+        rename(cfg, b, phis, dom_tree, stacks, counters);
+    }
+
+    for v in pushed {
+        stacks.get_mut(&v).unwrap().pop();
+    }
+}
+
+fn rename_vars(cfg : &mut Cfg, args : Vec<String>, phis : &mut HashMap<i32, Vec<Phi>>) {
+    let (entry, _) = cfg.block_map.first().unwrap();
+    let entry = *entry;
+    let mut stack = HashMap::new();
+    let dom_tree = form_dom_tree(cfg);
+    let defs = get_def_blocks(cfg);
+    
+    for v in defs.keys() {
+        stack.insert(v.to_string(), vec![]);
+    }
+
+    for v in &args {
+        stack.insert(v.to_string(), vec![v.to_string()]);
+    }
+    
+    rename(cfg, entry, phis, &dom_tree, &mut stack, &mut HashMap::new());
+}
+
+pub fn to_ssa(func : &mut AbstractFunction) {
+    to_ssa_mode(func, SsaMode::Pruned);
+}
+
+/// Converts a function into SSA form, placing phis as `mode` says. No dead
+/// code is removed afterwards, so the output shows every phi the mode
+/// places.
+pub fn to_ssa_mode(func : &mut AbstractFunction, mode : SsaMode) {
+    let blocks = form_blocks(func);
+    let mut cfg = form_cfg(blocks);
+    let args : Vec<_> = func.args.iter().map(|a| a.name.clone()).collect();
+    let mut phi_nodes = get_phi_nodes(&cfg, mode);
+    rename_vars(&mut cfg, args, &mut phi_nodes);
+    insert_phi_nodes(&mut cfg, phi_nodes);
+    func.instrs = reassemble(cfg);
+}
+
+/// A copy `dest = id src` that happens at the same time as the other copies
+/// on its edge.
+struct Copy {
+    dest : String,
+    src : String,
+    op_type : Option<AbstractType>,
+}
+
+fn id_instr(dest : &str, src : &str, op_type : &Option<AbstractType>) -> AbstractCode {
+    AbstractCode::Instruction(AbstractInstruction::Value {
+        args : vec![src.to_string()],
+        dest : dest.to_string(),
+        funcs : vec![],
+        labels : vec![],
+        op : "id".to_string(),
+        op_type : op_type.clone(),
+    })
+}
+
+/// Orders a set of parallel copies into `id` instructions with the same
+/// effect. A copy is emitted once no other pending copy still reads its
+/// destination; when only cycles remain, one destination is saved in a
+/// temporary first, which breaks its cycle.
+fn sequentialize(mut copies : Vec<Copy>, vars : &mut HashSet<String>) -> Vec<AbstractCode> {
+    copies.retain(|c| c.dest != c.src);
+    let mut instrs = vec![];
+    while !copies.is_empty() {
+        let ready = copies.iter().position(|c| copies.iter().all(|o| o.src != c.dest));
+        match ready {
+            Some(i) => {
+                let c = copies.remove(i);
+                instrs.push(id_instr(&c.dest, &c.src, &c.op_type));
+            },
+            None => {
+                let c = &copies[0];
+                let tmp = fresh(format!("{}.tmp", c.dest), vars.iter());
+                vars.insert(tmp.to_string());
+                instrs.push(id_instr(&tmp, &c.dest, &c.op_type));
+                let dest = c.dest.to_string();
+                for o in copies.iter_mut() {
+                    if o.src == dest {
+                        o.src = tmp.to_string();
+                    }
+                }
+            },
+        }
+    }
+    instrs
+}
+
+fn all_vars(func : &AbstractFunction) -> HashSet<String> {
+    let mut vars : HashSet<String> = func.args.iter().map(|a| a.name.to_string()).collect();
+    for code in &func.instrs {
+        if let AbstractCode::Instruction(instr) = code {
+            if let Some(dest) = get_dest(instr) {
+                vars.insert(dest.to_string());
+            }
+        }
+    }
+    vars
+}
+
+/// The copies the phis of `block` make along the edge from `pred`.
+fn phi_copies(cfg : &Cfg, block : i32, pred : i32) -> Vec<Copy> {
+    let label = cfg.name_map.get_by_left(&pred).unwrap();
+    let mut copies = vec![];
+    for code in &cfg.block_map.get(&block).unwrap().instrs {
+        if let AbstractCode::Instruction(AbstractInstruction::Value {op, dest, op_type, labels, args, ..}) = code {
+            if op != "phi" {
+                continue;
+            }
+            let src = labels.iter().position(|l| l == label).map(|i| &args[i]);
+            match src {
+                Some(src) if src != "__undefined" => copies.push(Copy {
+                    dest : dest.to_string(),
+                    src : src.to_string(),
+                    op_type : op_type.clone(),
+                }),
+                _ => (),
+            }
+        }
+    }
+    copies
+}
+
+fn is_phi(code : &AbstractCode) -> bool {
+    matches!(code, AbstractCode::Instruction(AbstractInstruction::Value {op, ..}) if op == "phi")
+}
+
+/// Removes phis whose values only flow into other dead phis. Minimal SSA
+/// has cycles of these around loops, and their copies could read variables
+/// that were never assigned.
+fn remove_dead_phis(cfg : &mut Cfg) {
+    let mut phi_args : HashMap<String, Vec<String>> = HashMap::new();
+    let mut worklist = vec![];
+    for block in cfg.block_map.values() {
+        for code in &block.instrs {
+            match code {
+                AbstractCode::Instruction(AbstractInstruction::Value {op, dest, args, ..}) if op == "phi" => {
+                    phi_args.insert(dest.to_string(), args.clone());
+                },
+                AbstractCode::Instruction(instr) => worklist.extend(get_args(instr).iter().cloned()),
+                AbstractCode::Label {..} => (),
+            }
+        }
+    }
+    let mut live = HashSet::new();
+    while let Some(v) = worklist.pop() {
+        if live.insert(v.to_string()) {
+            if let Some(args) = phi_args.get(&v) {
+                worklist.extend(args.iter().cloned());
+            }
+        }
+    }
+    for block in cfg.block_map.values_mut() {
+        block.instrs.retain(|code| match code {
+            AbstractCode::Instruction(AbstractInstruction::Value {op, dest, ..}) if op == "phi" =>
+                live.contains(dest),
+            _ => true,
+        });
+    }
+}
+
+/// Translates a function out of SSA form. Critical edges into blocks with
+/// phis are split first, so every edge has a block that only runs on that
+/// edge: the end of a predecessor with a single successor, or the start of a
+/// block with a single predecessor. The phis on each edge become parallel
+/// copies there, which `sequentialize` orders.
+pub fn from_ssa(func : &mut AbstractFunction) {
+    let mut vars = all_vars(func);
+    let blocks = form_blocks(func);
+    let mut cfg = form_cfg(blocks);
+    remove_dead_phis(&mut cfg);
+
+    let phi_blocks : Vec<i32> = cfg.block_map.iter()
+        .filter(|(_, block)| block.instrs.iter().any(is_phi))
+        .map(|(num, _)| *num).collect();
+    for block in &phi_blocks {
+        let mut preds = cfg.pred.get(block).unwrap().clone();
+        preds.sort();
+        preds.dedup();
+        for p in preds {
+            if cfg.is_critical_edge(p, *block) {
+                cfg.split_edge(p, *block);
+            }
+        }
+    }
+
+    for block in &phi_blocks {
+        let mut preds = cfg.pred.get(block).unwrap().clone();
+        preds.sort();
+        preds.dedup();
+        let single_pred = preds.len() == 1;
+        let mut entry_copies = vec![];
+        for p in preds {
+            let copies = sequentialize(phi_copies(&cfg, *block, p), &mut vars);
+            if single_pred {
+                entry_copies = copies;
+            } else {
+                let instrs = &mut cfg.block_map.get_mut(&p).unwrap().instrs;
+                let end = instrs.len() - 1;
+                instrs.splice(end..end, copies);
+            }
+        }
+        let instrs = &mut cfg.block_map.get_mut(block).unwrap().instrs;
+        instrs.retain(|code| !is_phi(code));
+        instrs.splice(0..0, entry_copies);
+    }
+
+    func.instrs = reassemble(cfg);
+    trivial_dce(func);
+}
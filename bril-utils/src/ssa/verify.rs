@@ -0,0 +1,193 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use bril_rs::{AbstractCode, AbstractFunction, AbstractInstruction};
+use crate::cfg::*;
+use crate::dominators::*;
+use crate::form_blocks::*;
+
+/// A way in which a function is not in SSA form. `index` is the position of
+/// the instruction in its block, not counting the label.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub func : String,
+    pub block : String,
+    pub index : usize,
+    pub message : String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@{} .{} [{}]: {}", self.func, self.block, self.index, self.message)
+    }
+}
+
+/// Where a variable is assigned. Arguments are assigned before the first
+/// instruction of the entry block.
+#[derive(Clone, Copy)]
+struct DefSite {
+    block : i32,
+    index : Option<usize>,
+}
+
+struct Verifier<'a> {
+    func : &'a str,
+    cfg : &'a Cfg,
+    dom_tree : DomTree,
+    reachable : HashSet<i32>,
+    defs : HashMap<String, DefSite>,
+    violations : Vec<Violation>,
+}
+
+impl<'a> Verifier<'a> {
+    fn report(&mut self, block : i32, index : usize, message : String) {
+        self.violations.push(Violation {
+            func : self.func.to_string(),
+            block : self.cfg.name(block).to_string(),
+            index,
+            message,
+        });
+    }
+
+    fn check_single_assignment(&mut self, args : &[String]) {
+        let entry = self.cfg.entry().unwrap();
+        for a in args {
+            self.defs.insert(a.to_string(), DefSite {block : entry, index : None});
+        }
+        for (num, block) in &self.cfg.block_map {
+            for (i, code) in block.instrs.iter().enumerate() {
+                let dest = match code {
+                    AbstractCode::Instruction(instr) => get_dest(instr),
+                    AbstractCode::Label {..} => None,
+                };
+                if let Some(dest) = dest {
+                    if self.defs.contains_key(dest) {
+                        self.report(*num, i, format!("`{dest}` is assigned more than once"));
+                    } else {
+                        self.defs.insert(dest.to_string(), DefSite {block : *num, index : Some(i)});
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether the definition at `def` is available right before
+    /// instruction `index` of `block`, or at the end of `block` when `index`
+    /// is `None`.
+    fn available(&self, def : DefSite, block : i32, index : Option<usize>) -> bool {
+        if def.block != block {
+            return self.dom_tree.dominates(&def.block, &block);
+        }
+        match (def.index, index) {
+            (None, _) | (_, None) => true,
+            (Some(d), Some(i)) => d < i,
+        }
+    }
+
+    fn check_use(&mut self, var : &String, block : i32, index : usize, at_end_of : Option<i32>) {
+        if var == "__undefined" {
+            self.report(block, index, "`__undefined` operand on a reachable path".to_string());
+            return;
+        }
+        let def = match self.defs.get(var) {
+            Some(def) => *def,
+            None => {
+                self.report(block, index, format!("`{var}` is never assigned"));
+                return;
+            },
+        };
+        let ok = match at_end_of {
+            Some(pred) => self.available(def, pred, None),
+            None => self.available(def, block, Some(index)),
+        };
+        if !ok {
+            let message = match at_end_of {
+                Some(pred) => format!("`{var}` does not dominate the end of .{}",
+                    self.cfg.name(pred)),
+                None => format!("`{var}` does not dominate this use"),
+            };
+            self.report(block, index, message);
+        }
+    }
+
+    fn check_phi(&mut self, block : i32, index : usize, args : &[String], labels : &[String]) {
+        if args.len() != labels.len() {
+            self.report(block, index, format!("phi has {} arguments but {} labels",
+                args.len(), labels.len()));
+        }
+        let preds : HashSet<&String> = self.cfg.pred.get(&block).unwrap().iter()
+            .map(|p| self.cfg.name(*p)).collect();
+        let mut seen = HashSet::new();
+        let mut messages = vec![];
+        for l in labels {
+            if !preds.contains(l) {
+                messages.push(format!("phi label .{l} is not a predecessor"));
+            } else if !seen.insert(l) {
+                messages.push(format!("phi label .{l} appears more than once"));
+            }
+        }
+        let mut missing : Vec<&&String> = preds.iter().filter(|p| !seen.contains(**p)).collect();
+        missing.sort();
+        for p in missing {
+            messages.push(format!("phi has no label for predecessor .{p}"));
+        }
+        for message in messages {
+            self.report(block, index, message);
+        }
+
+        for (a, l) in args.iter().zip(labels) {
+            match self.cfg.name_map.get_by_right(l) {
+                Some(pred) if self.reachable.contains(pred) => {
+                    let pred = *pred;
+                    self.check_use(a, block, index, Some(pred));
+                },
+                _ => (),
+            }
+        }
+    }
+
+    fn check_uses(&mut self) {
+        let mut blocks : Vec<i32> = self.reachable.iter().copied().collect();
+        blocks.sort_by_key(|b| self.cfg.block_map.get_index_of(b));
+        for num in blocks {
+            let block = self.cfg.block_map.get(&num).unwrap();
+            for (i, code) in block.instrs.iter().enumerate() {
+                match code {
+                    AbstractCode::Instruction(AbstractInstruction::Value {op, args, labels, ..})
+                        if op == "phi" => self.check_phi(num, i, args, labels),
+                    AbstractCode::Instruction(instr) => {
+                        let mut seen = HashSet::new();
+                        for a in get_args(instr) {
+                            if seen.insert(a) {
+                                self.check_use(a, num, i, None);
+                            }
+                        }
+                    },
+                    AbstractCode::Label {..} => (),
+                }
+            }
+        }
+    }
+}
+
+/// Checks that `func` is in SSA form: every variable is assigned once, every
+/// use is dominated by its definition (a phi operand by the end of the
+/// matching predecessor), phi labels are exactly the predecessors of their
+/// block and no `__undefined` operand is left on a reachable path.
+pub fn verify_ssa(func : &AbstractFunction) -> Vec<Violation> {
+    let cfg = form_cfg(form_blocks(func));
+    if cfg.block_map.is_empty() {
+        return vec![];
+    }
+    let args : Vec<String> = func.args.iter().map(|a| a.name.clone()).collect();
+    let mut verifier = Verifier {
+        func : &func.name,
+        dom_tree : form_dom_tree(&cfg),
+        reachable : cfg.reachable(),
+        cfg : &cfg,
+        defs : HashMap::new(),
+        violations : vec![],
+    };
+    verifier.check_single_assignment(&args);
+    verifier.check_uses();
+    verifier.violations
+}
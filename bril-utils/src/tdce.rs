@@ -1,90 +1,67 @@
-use crate::form_blocks::*;
-use std::collections::{HashSet, HashMap};
+use crate::ir::{with_ir, Function, VarId};
+use std::collections::HashMap;
 use bril_rs::*;
 
-pub fn trivial_global_dce_pass(func : &mut AbstractFunction) -> bool {
-    let mut blocks = form_blocks(&func);
-    let mut used = HashSet::new();
-    for block in &blocks {
-        for code in &block.instrs {
-            match code {
-                AbstractCode::Instruction(instr) =>
-                    match instr {
-                        AbstractInstruction::Value {args, ..} => used.extend(args.clone()),
-                        AbstractInstruction::Effect {args, ..} => used.extend(args.clone()),
-                        _ => (),
-                    },
-                _ => (),
-            }
+/// Removes instructions whose destination is not read anywhere in the
+/// function.
+fn global_dce(func : &mut Function) -> bool {
+    let mut used = vec![false; func.vars.len()];
+    for (_, i) in func.insts() {
+        for a in &func.inst(i).args {
+            used[a.index()] = true;
         }
     }
+    func.retain_insts(|inst| inst.dest.is_none_or(|d| used[d.index()]))
+}
 
-    let mut not_done = false;
-    
-    for block in &mut blocks {
-        let mut new_block = Block { instrs: Vec::new() };
-        for code in &block.instrs {
-            match code {
-                AbstractCode::Instruction(instr) =>
-                    match instr {
-                        AbstractInstruction::Constant {dest, ..} | AbstractInstruction::Value {dest, ..} => {
-                            if !used.contains(dest) {
-                                not_done = true;
-                            } else {
-                                new_block.instrs.push(code.clone())
-                            }
-                        },
-                        AbstractInstruction::Effect {..} => new_block.instrs.push(code.clone()),
-                    }
-                AbstractCode::Label {..} => new_block.instrs.push(code.clone()),
+/// Removes instructions whose destination is overwritten later in the same
+/// block before anything reads it.
+fn local_dce(func : &mut Function) -> bool {
+    let mut changed = false;
+    let mut last_def : HashMap<VarId, usize> = HashMap::new();
+    for b in func.layout.clone() {
+        last_def.clear();
+        let mut dead = vec![false; func.block(b).insts.len()];
+        for (pos, i) in func.block(b).insts.iter().enumerate() {
+            let inst = func.inst(*i);
+            for a in &inst.args {
+                last_def.remove(a);
+            }
+            if let Some(dest) = inst.dest {
+                if let Some(prev) = last_def.insert(dest, pos) {
+                    dead[prev] = true;
+                    changed = true;
+                }
             }
         }
-        *block = new_block;
+        let mut pos = 0;
+        func.block_mut(b).insts.retain(|_| {
+            pos += 1;
+            !dead[pos - 1]
+        });
     }
-    func.instrs = flatten_blocks(blocks);
-    not_done
+    changed
 }
 
-pub fn locally_killed_instrs_pass(func : &mut AbstractFunction) -> bool {
-    let mut blocks = form_blocks(&func);
-    let mut not_done = false;
-    for block in &mut blocks {
-        let mut to_remove : Vec<usize> = Vec::new();
-        let mut last_def = HashMap::new();
-        for i in 0..block.instrs.len() {
-            let code = &block.instrs[i];
-            match code {
-                AbstractCode::Instruction(instr) => {
-                    match instr {
-                        AbstractInstruction::Value {args, ..} | AbstractInstruction::Effect {args, ..} => {
-                            for a in args {
-                                last_def.remove(&a);
-                            }
-                        },
-                        _ => (),
-                    }
-                    match instr {
-                        AbstractInstruction::Value {dest, ..} | AbstractInstruction::Constant {dest, ..} => {
-                            if last_def.contains_key(&dest) {
-                                to_remove.push(*last_def.get(&dest).unwrap());
-                                not_done = true;
-                            }
-                            last_def.insert(dest, i);
-                        },
-                        _ => (),
-                    }
-                }
-                _ => (),
-            }
-        }
-        for i in to_remove {
-            block.instrs.remove(i);
-        }
+/// Runs both dce passes until neither removes anything. Returns whether
+/// any instruction was removed.
+pub fn trivial_dce_ir(func : &mut Function) -> bool {
+    let mut changed = false;
+    while global_dce(func) || local_dce(func) {
+        changed = true;
     }
-    func.instrs = flatten_blocks(blocks);
-    not_done
+    changed
+}
+
+pub fn trivial_global_dce_pass(func : &mut AbstractFunction) -> bool {
+    with_ir(func, global_dce)
+}
+
+pub fn locally_killed_instrs_pass(func : &mut AbstractFunction) -> bool {
+    with_ir(func, local_dce)
 }
 
-pub fn trivial_dce(func : &mut AbstractFunction) {
-    while trivial_global_dce_pass(func) || locally_killed_instrs_pass(func) {}
+/// See `trivial_dce_ir`.
+pub fn trivial_dce(func : &mut AbstractFunction) -> bool {
+    with_ir(func, trivial_dce_ir)
 }
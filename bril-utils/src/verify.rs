@@ -0,0 +1,501 @@
+use bril_rs::{AbstractCode, AbstractFunction, AbstractInstruction, AbstractProgram, AbstractType, Literal};
+
+use crate::cfg::{Cfg, form_cfg};
+use crate::df::{df_analysis, Confluence, Dataflow, Direction, ProgramPoint};
+use crate::form_blocks::{form_blocks, get_args, get_dest, is_terminator};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// What is wrong with an instruction or a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    DuplicateLabel(String),
+    UnknownLabel(String),
+    UnknownFunction(String),
+    UnknownOp(String),
+    /// `op` has `found` operands of kind `operand` (argument, label or
+    /// function) instead of `expected`.
+    WrongArity {op : String, operand : &'static str, expected : usize, found : usize},
+    /// The function has a return type but can reach its end without `ret`.
+    MissingReturn,
+    /// The variable may be read before it is assigned.
+    Unassigned(String),
+    MissingType(String),
+    TypeMismatch {var : String, expected : AbstractType, found : AbstractType},
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticKind::DuplicateLabel(l) => write!(f, "label .{l} is defined more than once"),
+            DiagnosticKind::UnknownLabel(l) => write!(f, "label .{l} does not exist"),
+            DiagnosticKind::UnknownFunction(g) => write!(f, "function @{g} does not exist"),
+            DiagnosticKind::UnknownOp(op) => write!(f, "unknown operation `{op}`"),
+            DiagnosticKind::WrongArity {op, operand, expected, found} => {
+                let plural = if *expected == 1 { "" } else { "s" };
+                write!(f, "`{op}` takes {expected} {operand}{plural} but has {found}")
+            },
+            DiagnosticKind::MissingReturn => write!(f, "control can reach the end of the function without a `ret`"),
+            DiagnosticKind::Unassigned(v) => write!(f, "`{v}` may be used before it is assigned"),
+            DiagnosticKind::MissingType(v) => write!(f, "`{v}` has no type"),
+            DiagnosticKind::TypeMismatch {var, expected, found} =>
+                write!(f, "`{var}` has type {found} where {expected} is expected"),
+        }
+    }
+}
+
+/// A problem found in function `func`. `index` is the position of the
+/// instruction in `func.instrs`, labels included, or `None` when the problem
+/// is not tied to one instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub func : String,
+    pub index : Option<usize>,
+    pub kind : DiagnosticKind,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.index {
+            Some(i) => write!(f, "@{} [{i}]: {}", self.func, self.kind),
+            None => write!(f, "@{}: {}", self.func, self.kind),
+        }
+    }
+}
+
+/// Variables assigned on every path to each point.
+struct AssignedVars {
+    args : HashSet<String>,
+}
+
+impl Dataflow for AssignedVars {
+    type Domain = HashSet<String>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn confluence(&self) -> Confluence {
+        Confluence::Meet
+    }
+
+    fn top(&self, cfg : &Cfg) -> Self::Domain {
+        let mut vars = self.args.clone();
+        for block in cfg.block_map.values() {
+            for code in &block.instrs {
+                if let AbstractCode::Instruction(instr) = code {
+                    vars.extend(get_dest(instr).cloned());
+                }
+            }
+        }
+        vars
+    }
+
+    fn bottom(&self, _cfg : &Cfg) -> Self::Domain {
+        HashSet::new()
+    }
+
+    fn boundary(&self, _cfg : &Cfg) -> Self::Domain {
+        self.args.clone()
+    }
+
+    fn transfer(&self, _point : ProgramPoint, instr : &AbstractInstruction,
+        in_b : &Self::Domain) -> Self::Domain {
+        let mut set = in_b.clone();
+        if let Some(dest) = get_dest(instr) {
+            set.insert(dest.to_string());
+        }
+        set
+    }
+}
+
+fn prim(name : &str) -> AbstractType {
+    AbstractType::Primitive(name.to_string())
+}
+
+/// Argument and result types of the operations whose types are fixed.
+fn signature(op : &str) -> Option<(&'static [&'static str], &'static str)> {
+    match op {
+        "add" | "mul" | "sub" | "div" => Some((&["int", "int"], "int")),
+        "eq" | "lt" | "gt" | "le" | "ge" => Some((&["int", "int"], "bool")),
+        "and" | "or" => Some((&["bool", "bool"], "bool")),
+        "not" => Some((&["bool"], "bool")),
+        "fadd" | "fmul" | "fsub" | "fdiv" => Some((&["float", "float"], "float")),
+        "feq" | "flt" | "fgt" | "fle" | "fge" => Some((&["float", "float"], "bool")),
+        _ => None,
+    }
+}
+
+/// Number of arguments, labels and functions each operation takes. `None`
+/// for the arguments means any number. `ret`, `call` and `phi` are checked
+/// separately.
+fn arity(op : &str) -> Option<(Option<usize>, usize, usize)> {
+    if let Some((args, _)) = signature(op) {
+        return Some((Some(args.len()), 0, 0));
+    }
+    match op {
+        "id" | "alloc" | "load" | "free" => Some((Some(1), 0, 0)),
+        "ptradd" | "store" => Some((Some(2), 0, 0)),
+        "jmp" => Some((Some(0), 1, 0)),
+        "br" => Some((Some(1), 2, 0)),
+        "guard" => Some((Some(1), 1, 0)),
+        "nop" | "speculate" | "commit" => Some((Some(0), 0, 0)),
+        "print" => Some((None, 0, 0)),
+        "ret" | "call" | "phi" => Some((None, 0, 0)),
+        _ => None,
+    }
+}
+
+fn literal_type(value : &Literal) -> AbstractType {
+    match value {
+        Literal::Int(_) => prim("int"),
+        Literal::Bool(_) => prim("bool"),
+        Literal::Float(_) => prim("float"),
+    }
+}
+
+fn pointee(t : &AbstractType) -> Option<&AbstractType> {
+    match t {
+        AbstractType::Parameterized(p, inner) if p == "ptr" => Some(inner),
+        _ => None,
+    }
+}
+
+struct Checker<'a> {
+    func : &'a AbstractFunction,
+    funcs : &'a HashMap<&'a str, &'a AbstractFunction>,
+    types : HashMap<String, AbstractType>,
+    diagnostics : Vec<Diagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    fn report(&mut self, index : Option<usize>, kind : DiagnosticKind) {
+        self.diagnostics.push(Diagnostic {func : self.func.name.to_string(), index, kind});
+    }
+
+    /// Checks that the labels are unique and that every label used exists.
+    /// Returns whether the CFG can be formed.
+    fn check_labels(&mut self) -> bool {
+        let mut labels = HashSet::new();
+        for (i, code) in self.func.instrs.iter().enumerate() {
+            if let AbstractCode::Label {label} = code {
+                if !labels.insert(label) {
+                    self.report(Some(i), DiagnosticKind::DuplicateLabel(label.to_string()));
+                }
+            }
+        }
+        let mut ok = self.diagnostics.is_empty();
+        for (i, code) in self.func.instrs.iter().enumerate() {
+            if let AbstractCode::Instruction(AbstractInstruction::Value {labels : used, ..}
+                | AbstractInstruction::Effect {labels : used, ..}) = code {
+                for l in used {
+                    if !labels.contains(l) {
+                        self.report(Some(i), DiagnosticKind::UnknownLabel(l.to_string()));
+                        ok = false;
+                    }
+                }
+            }
+        }
+        ok
+    }
+
+    fn check_count(&mut self, i : usize, op : &str, operand : &'static str, expected : usize, found : usize) {
+        if expected != found {
+            self.report(Some(i), DiagnosticKind::WrongArity {
+                op : op.to_string(), operand, expected, found});
+        }
+    }
+
+    fn check_arity(&mut self, i : usize, instr : &AbstractInstruction) {
+        let (op, args, labels, funcs) = match instr {
+            AbstractInstruction::Constant {..} => return,
+            AbstractInstruction::Value {op, args, labels, funcs, ..}
+            | AbstractInstruction::Effect {op, args, labels, funcs, ..} => (op, args, labels, funcs),
+        };
+        let (n_args, n_labels, n_funcs) = match arity(op) {
+            Some(a) => a,
+            None => {
+                self.report(Some(i), DiagnosticKind::UnknownOp(op.to_string()));
+                return;
+            },
+        };
+        if let Some(n) = n_args {
+            self.check_count(i, op, "argument", n, args.len());
+        }
+        match op.as_str() {
+            "phi" => self.check_count(i, op, "label", args.len(), labels.len()),
+            "call" => self.check_count(i, op, "function", 1, funcs.len()),
+            _ => {
+                self.check_count(i, op, "label", n_labels, labels.len());
+                self.check_count(i, op, "function", n_funcs, funcs.len());
+            },
+        }
+        if op == "ret" {
+            let expected = usize::from(self.func.return_type.is_some());
+            self.check_count(i, op, "argument", expected, args.len());
+        }
+    }
+
+    /// Records the type of every variable, reporting definitions that
+    /// disagree with an earlier one.
+    fn collect_types(&mut self) {
+        for a in &self.func.args {
+            self.types.insert(a.name.to_string(), a.arg_type.clone());
+        }
+        for (i, code) in self.func.instrs.iter().enumerate() {
+            let (dest, t) = match code {
+                AbstractCode::Instruction(AbstractInstruction::Constant {dest, const_type, ..}) => (dest, const_type),
+                AbstractCode::Instruction(AbstractInstruction::Value {dest, op_type, ..}) => (dest, op_type),
+                _ => continue,
+            };
+            match (t, self.types.get(dest)) {
+                (None, _) => self.report(Some(i), DiagnosticKind::MissingType(dest.to_string())),
+                (Some(t), Some(expected)) if t != expected => {
+                    let expected = expected.clone();
+                    self.report(Some(i), DiagnosticKind::TypeMismatch {
+                        var : dest.to_string(), expected, found : t.clone()});
+                },
+                (Some(t), _) => {
+                    self.types.insert(dest.to_string(), t.clone());
+                },
+            }
+        }
+    }
+
+    fn expect(&mut self, i : usize, var : &str, expected : &AbstractType) {
+        if let Some(found) = self.types.get(var) {
+            if found != expected {
+                let found = found.clone();
+                self.report(Some(i), DiagnosticKind::TypeMismatch {
+                    var : var.to_string(), expected : expected.clone(), found});
+            }
+        }
+    }
+
+    fn check_call(&mut self, i : usize, name : &str, args : &[String], dest : Option<(&String, &Option<AbstractType>)>) {
+        let callee = match self.funcs.get(name) {
+            Some(callee) => *callee,
+            None => {
+                self.report(Some(i), DiagnosticKind::UnknownFunction(name.to_string()));
+                return;
+            },
+        };
+        self.check_count(i, "call", "argument", callee.args.len(), args.len());
+        for (a, param) in args.iter().zip(&callee.args) {
+            self.expect(i, a, &param.arg_type);
+        }
+        if let Some((dest, Some(t))) = dest {
+            match &callee.return_type {
+                Some(ret) if ret != t => self.report(Some(i), DiagnosticKind::TypeMismatch {
+                    var : dest.to_string(), expected : ret.clone(), found : t.clone()}),
+                None => self.report(Some(i), DiagnosticKind::WrongArity {
+                    op : "call".to_string(), operand : "result", expected : 0, found : 1}),
+                _ => (),
+            }
+        }
+    }
+
+    fn check_types(&mut self, i : usize, instr : &AbstractInstruction) {
+        match instr {
+            AbstractInstruction::Constant {dest, const_type : Some(t), value, ..} => {
+                let found = literal_type(value);
+                // Integer literals are also valid floats.
+                if found != *t && !(found == prim("int") && *t == prim("float")) {
+                    self.report(Some(i), DiagnosticKind::TypeMismatch {
+                        var : dest.to_string(), expected : t.clone(), found});
+                }
+            },
+            AbstractInstruction::Constant {..} => (),
+            AbstractInstruction::Value {op, args, dest, funcs, op_type, ..} => {
+                let t = match op_type {
+                    Some(t) => t.clone(),
+                    None => return,
+                };
+                if let Some((arg_types, result)) = signature(op) {
+                    for (a, at) in args.iter().zip(arg_types) {
+                        self.expect(i, a, &prim(at));
+                    }
+                    self.expect(i, dest, &prim(result));
+                    return;
+                }
+                match op.as_str() {
+                    "id" if !args.is_empty() => self.expect(i, &args[0], &t),
+                    "phi" => {
+                        for a in args.iter().filter(|a| *a != "__undefined") {
+                            self.expect(i, a, &t);
+                        }
+                    },
+                    "call" if !funcs.is_empty() => self.check_call(i, &funcs[0], args, Some((dest, op_type))),
+                    "alloc" if !args.is_empty() => self.expect(i, &args[0], &prim("int")),
+                    "load" if !args.is_empty() =>
+                        self.expect(i, &args[0], &AbstractType::Parameterized("ptr".to_string(), Box::new(t))),
+                    "ptradd" if args.len() == 2 => {
+                        self.expect(i, &args[0], &t);
+                        self.expect(i, &args[1], &prim("int"));
+                    },
+                    _ => (),
+                }
+            },
+            AbstractInstruction::Effect {op, args, funcs, ..} => match op.as_str() {
+                "br" | "guard" if !args.is_empty() => self.expect(i, &args[0], &prim("bool")),
+                "ret" if args.len() == 1 => {
+                    if let Some(t) = self.func.return_type.clone() {
+                        self.expect(i, &args[0], &t);
+                    }
+                },
+                "call" if !funcs.is_empty() => self.check_call(i, &funcs[0], args, None),
+                "store" if args.len() == 2 => {
+                    let inner = self.types.get(&args[0]).and_then(pointee).cloned();
+                    if let Some(inner) = inner {
+                        self.expect(i, &args[1], &inner);
+                    }
+                },
+                _ => (),
+            },
+        }
+    }
+
+    /// Reports reads of variables that are not assigned on every path, and
+    /// paths that fall off the end of a function that returns a value.
+    /// Unreachable code is not checked.
+    fn check_assignment(&mut self, cfg : &Cfg) {
+        let positions = raw_positions(self.func, cfg);
+        let df = AssignedVars {args : self.func.args.iter().map(|a| a.name.to_string()).collect()};
+        let result = df_analysis(cfg, &df);
+        let reachable = cfg.reachable();
+        let mut found = vec![];
+        for (num, block) in cfg.block_map.iter().filter(|(num, _)| reachable.contains(num)) {
+            let mut assigned = result.block_in(*num).clone();
+            for (i, code) in block.instrs.iter().enumerate() {
+                let instr = match code {
+                    AbstractCode::Instruction(instr) => instr,
+                    AbstractCode::Label {..} => continue,
+                };
+                let point = ProgramPoint::new(*num, i);
+                let index = positions.get(&point).copied();
+                match instr {
+                    AbstractInstruction::Value {op, args, labels, ..} if op == "phi" => {
+                        for (a, l) in args.iter().zip(labels) {
+                            let pred = cfg.name_map.get_by_right(l);
+                            let ok = a == "__undefined" || pred.is_none_or(|p|
+                                !reachable.contains(p) || result.block_out(*p).contains(a));
+                            if !ok {
+                                found.push((index, DiagnosticKind::Unassigned(a.to_string())));
+                            }
+                        }
+                    },
+                    _ => {
+                        for a in get_args(instr) {
+                            if !assigned.contains(a) {
+                                found.push((index, DiagnosticKind::Unassigned(a.to_string())));
+                            }
+                        }
+                    },
+                }
+                if let AbstractInstruction::Effect {op, ..} = instr {
+                    if op == "ret" && index.is_none() && self.func.return_type.is_some() {
+                        found.push((None, DiagnosticKind::MissingReturn));
+                    }
+                }
+                assigned = df.transfer(point, instr, &assigned);
+            }
+        }
+        found.sort_by_key(|(index, _)| *index);
+        found.dedup();
+        for (index, kind) in found {
+            self.report(index, kind);
+        }
+    }
+}
+
+/// Maps the instructions of `cfg`, formed from `func`, back to their
+/// positions in `func.instrs`. Instructions added by `form_cfg` are missing.
+fn raw_positions(func : &AbstractFunction, cfg : &Cfg) -> HashMap<ProgramPoint, usize> {
+    let offset = cfg.block_map.len() - form_blocks(func).len();
+    let mut positions = HashMap::new();
+    let mut ordinal = offset;
+    let mut index = 0;
+    let mut started = false;
+    for (i, code) in func.instrs.iter().enumerate() {
+        match code {
+            AbstractCode::Label {..} => {
+                if started {
+                    ordinal += 1;
+                }
+                started = true;
+                index = 0;
+            },
+            AbstractCode::Instruction(instr) => {
+                if !started {
+                    started = true;
+                    index = 0;
+                }
+                let (num, _) = cfg.block_map.get_index(ordinal).unwrap();
+                positions.insert(ProgramPoint::new(*num, index), i);
+                index += 1;
+                if is_terminator(instr) {
+                    ordinal += 1;
+                    started = false;
+                }
+            },
+        }
+    }
+    positions
+}
+
+fn verify_function_in(func : &AbstractFunction, funcs : &HashMap<&str, &AbstractFunction>) -> Vec<Diagnostic> {
+    let mut checker = Checker {func, funcs, types : HashMap::new(), diagnostics : vec![]};
+    let labels_ok = checker.check_labels();
+    checker.collect_types();
+    for (i, code) in func.instrs.iter().enumerate() {
+        if let AbstractCode::Instruction(instr) = code {
+            checker.check_arity(i, instr);
+            checker.check_types(i, instr);
+        }
+    }
+    if labels_ok && !func.instrs.is_empty() {
+        let cfg = form_cfg(form_blocks(func));
+        checker.check_assignment(&cfg);
+    } else if func.instrs.is_empty() && func.return_type.is_some() {
+        checker.report(None, DiagnosticKind::MissingReturn);
+    }
+    checker.diagnostics.sort_by_key(|d| d.index);
+    checker.diagnostics
+}
+
+/// Checks a single function. Calls are only checked against the function
+/// itself; use `verify_program` to resolve calls to other functions.
+pub fn verify_function(func : &AbstractFunction) -> Vec<Diagnostic> {
+    let funcs = HashMap::from([(func.name.as_str(), func)]);
+    verify_function_in(func, &funcs)
+}
+
+/// Checks `func`, resolving calls against the functions of `program`. `func`
+/// itself need not be in `program`, or may be an older version of it.
+pub fn verify_function_against(func : &AbstractFunction, program : &AbstractProgram) -> Vec<Diagnostic> {
+    let mut funcs : HashMap<&str, &AbstractFunction> = program.functions.iter()
+        .map(|f| (f.name.as_str(), f)).collect();
+    funcs.insert(func.name.as_str(), func);
+    verify_function_in(func, &funcs)
+}
+
+/// Checks that every function of `program` is well formed: labels are unique
+/// and resolve, every path of a function with a return type ends in `ret`,
+/// operations have the right number of arguments, labels and functions,
+/// variables are assigned on every path before they are read, and types
+/// agree between definitions, uses and signatures.
+pub fn verify_program(program : &AbstractProgram) -> Vec<Diagnostic> {
+    let funcs : HashMap<&str, &AbstractFunction> = program.functions.iter()
+        .map(|f| (f.name.as_str(), f)).collect();
+    program.functions.iter().flat_map(|f| verify_function_in(f, &funcs)).collect()
+}
+
+/// Prints the diagnostics for `program` to stderr, prefixed with `stage`.
+/// Returns whether there were any.
+pub fn report(stage : &str, program : &AbstractProgram) -> bool {
+    let diagnostics = verify_program(program);
+    for d in &diagnostics {
+        eprintln!("{stage}: {d}");
+    }
+    !diagnostics.is_empty()
+}
@@ -1,4 +0,0 @@
-pub mod form_blocks;
-pub mod tdce;
-pub mod lvn;
-// pub mod literal;
@@ -1,5 +1,6 @@
 use bril_rs::{load_abstract_program, output_abstract_program};
-use passes::{lvn::local_value_numbering, tdce::trivial_dce};
+use bril_utils::{lvn::{local_value_numbering, parse_identities}, gvn::global_value_numbering, tdce::trivial_dce};
+use bril_utils::verify;
 use clap::Parser;
 
 #[derive(Parser, Debug)]
@@ -15,16 +16,54 @@ struct Args {
 
     #[clap(short, long)]
     no_dce : bool,
+
+    #[clap(short, long)]
+    gvn : bool,
+
+    /// Apply algebraic identities: all of them, or a comma-separated list of
+    /// add-zero, sub-zero, sub-self, mul-zero, mul-one, div-one, cmp-self,
+    /// logic-self, logic-const and not-not.
+    #[clap(short, long, min_values = 0)]
+    algebra : Option<Option<String>>,
+
+    /// Turn multiplications by a power of two into additions.
+    #[clap(short, long)]
+    strength : bool,
+
+    /// Check for malformed Bril before and after the pass. Diagnostics go
+    /// to stderr and the exit code is 1 if the output has any.
+    #[clap(long)]
+    lint : bool,
 }
 
 fn main() {
     let args = Args::parse();
+    let algebra = match &args.algebra {
+        None => Default::default(),
+        Some(rules) => match parse_identities(rules.as_deref().unwrap_or("all")) {
+            Ok(rules) => rules,
+            Err(e) => {
+                eprintln!("error: {e}");
+                std::process::exit(2);
+            },
+        },
+    };
     let mut program = load_abstract_program();
+    if args.lint {
+        verify::report("input", &program);
+    }
     for f in &mut program.functions {
-        local_value_numbering(f, args.prop, args.comm, args.fold);
+        if args.gvn {
+            global_value_numbering(f, args.prop, args.comm);
+        } else {
+            local_value_numbering(f, args.prop, args.comm, args.fold, &algebra, args.strength);
+        }
         if !args.no_dce {
             trivial_dce(f);
         }
     }
-    output_abstract_program(&program)
+    output_abstract_program(&program);
+    if args.lint && verify::report("output", &program) {
+        std::process::exit(1);
+    }
 }
@@ -1,2 +0,0 @@
-pub mod form_blocks;
-pub mod tdce;
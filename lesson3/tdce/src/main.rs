@@ -1,10 +1,41 @@
 use bril_rs::{load_abstract_program, output_abstract_program};
-use passes::tdce::trivial_dce;
+use bril_utils::adce::adce;
+use bril_utils::simplify_cfg::simplify_cfg;
+use bril_utils::tdce::trivial_dce;
+use bril_utils::verify;
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(short, long)]
+    aggressive : bool,
+    #[clap(short, long)]
+    simplify : bool,
+
+    /// Check for malformed Bril before and after the pass. Diagnostics go
+    /// to stderr and the exit code is 1 if the output has any.
+    #[clap(long)]
+    lint : bool,
+}
 
 fn main() {
+    let args = Args::parse();
     let mut program = load_abstract_program();
+    if args.lint {
+        verify::report("input", &program);
+    }
     for func in &mut program.functions {
-        trivial_dce(func);
+        if args.aggressive {
+            adce(func);
+        } else {
+            trivial_dce(func);
+        }
+        if args.simplify {
+            simplify_cfg(func);
+        }
+    }
+    output_abstract_program(&program);
+    if args.lint && verify::report("output", &program) {
+        std::process::exit(1);
     }
-    output_abstract_program(&program)
 }
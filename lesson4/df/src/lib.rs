@@ -1,3 +0,0 @@
-pub mod cfg;
-pub mod form_blocks;
-pub mod df;
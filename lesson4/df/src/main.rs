@@ -1,8 +1,14 @@
-use df::cfg::*;
-use df::form_blocks::*;
-use df::df::*;
+use bril_utils::cfg::*;
+use bril_utils::form_blocks::*;
+use bril_utils::df::*;
+use bril_utils::chains::def_use_chains;
+use bril_utils::dot::*;
+use bril_utils::bitset::BitSet;
+use bril_utils::ir::{Function, VarId};
+use bril_utils::liveness::*;
 use bril_rs::load_abstract_program;
 use clap::Parser;
+use indexmap::IndexMap;
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -11,19 +17,130 @@ struct Args {
 
     #[clap(short, long)]
     decl : bool,
+
+    /// Print the results as JSON, keyed by function and then by block.
+    #[clap(short, long)]
+    json : bool,
+
+    /// Print each function's CFG as a Graphviz graph with the in and out
+    /// sets on every block.
+    #[clap(long)]
+    dot : bool,
+
+    /// Print the variables live after every instruction.
+    #[clap(short, long)]
+    points : bool,
+
+    /// Print the live range of every variable.
+    #[clap(short, long)]
+    ranges : bool,
+
+    /// Print the interference graph and the copies between variables.
+    #[clap(short, long)]
+    interference : bool,
+
+    /// Print the definitions reaching each block and the uses of every
+    /// definition.
+    #[clap(long)]
+    reaching : bool,
+}
+
+fn names(func : &Function, set : &BitSet) -> Vec<String> {
+    let mut names : Vec<String> = set.iter().map(|v| func.vars.name(VarId::new(v)).to_string()).collect();
+    names.sort();
+    names
+}
+
+fn print_liveness(func : &Function, args : &Args) {
+    let live = Liveness::new(func);
+    println!("@{}", func.name);
+    if args.points {
+        for b in &func.layout {
+            println!("{}:", func.block(*b).name);
+            println!("    in: {:?}", names(func, live.live_in(*b)));
+            for i in &func.block(*b).insts {
+                let instr = func.instr_to_abstract(func.inst(*i));
+                println!("    {instr}  # live: {:?}", names(func, live.live_after(*i)));
+            }
+        }
+    } else if args.ranges {
+        for range in live.live_ranges(func) {
+            if !range.intervals.is_empty() {
+                let intervals : Vec<String> = range.intervals.iter().map(|(s, e)| format!("[{s}, {e}]")).collect();
+                println!("{}: {}", func.vars.name(range.var), intervals.join(" "));
+            }
+        }
+    } else {
+        let graph = InterferenceGraph::new(func, &live);
+        for v in 0..graph.num_vars() {
+            let v = VarId::new(v);
+            let mut neighbors : Vec<&str> = graph.neighbors(v).map(|n| func.vars.name(n)).collect();
+            neighbors.sort();
+            println!("{}: {}", func.vars.name(v), neighbors.join(", "));
+        }
+        for (dest, src) in &graph.moves {
+            println!("move {} <- {}", func.vars.name(*dest), func.vars.name(*src));
+        }
+    }
 }
 
 
+fn def_name(cfg : &Cfg, def : &Def) -> String {
+    match def.point {
+        Some(point) => format!("{}@{}.{}", def.var, cfg.name(point.block), point.index),
+        None => format!("{}@arg", def.var),
+    }
+}
+
+fn print_reaching(name : &str, cfg : &Cfg, args : &[String]) {
+    let reaching = ReachingDefs::new(cfg, args);
+    let chains = def_use_chains(cfg, args);
+    let show = |defs : Vec<Def>| defs.iter().map(|d| def_name(cfg, d)).collect::<Vec<_>>().join(", ");
+    println!("@{name}");
+    for num in cfg.block_map.keys() {
+        println!("{}:", cfg.name(*num));
+        println!("    in: [{}]", show(reaching.block_in(*num)));
+        println!("    out: [{}]", show(reaching.block_out(*num)));
+    }
+    println!("uses:");
+    for def in reaching.defs() {
+        let uses : Vec<String> = chains.uses(def).iter()
+            .map(|u| format!("{}.{}", cfg.name(u.point.block), u.point.index)).collect();
+        println!("    {} -> [{}]", def_name(cfg, def), uses.join(", "));
+    }
+}
+
 fn main() {
     let args = Args::parse();
     let mut program = load_abstract_program();
+    let mut reports = IndexMap::new();
     for func in &mut program.functions {
+        if args.points || args.ranges || args.interference {
+            print_liveness(&Function::from_abstract(func), &args);
+            continue;
+        }
         let blocks = form_blocks(&func);
         let cfg = form_cfg(blocks);
-        if args.live {
-            live_vars(&cfg);
+        if args.reaching {
+            let names : Vec<String> = func.args.iter().map(|a| a.name.clone()).collect();
+            print_reaching(&func.name, &cfg, &names);
+            continue;
+        }
+        let report = if args.live {
+            live_vars(&cfg)
+        } else {
+            declared_vars(&cfg)
+        };
+        if args.dot {
+            let overlays = Overlays {facts : Some(&report), ..Overlays::default()};
+            print!("{}", cfg_to_dot(&func.name, &cfg, &overlays));
+        } else if args.json {
+            reports.insert(func.name.clone(), report);
         } else {
-            declared_vars(&cfg);
+            print!("{report}");
         }
     }
+    if args.json && !args.dot {
+        println!("{}", serde_json::to_string_pretty(&reports).unwrap());
+    }
 }
@@ -1,4 +0,0 @@
-pub mod cfg;
-pub mod form_blocks;
-pub mod dominators;
-pub mod test;
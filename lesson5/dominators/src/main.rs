@@ -1,9 +1,12 @@
-use dominators::cfg::*;
-use dominators::form_blocks::*;
-use dominators::dominators::*;
-use dominators::test::validate_dominators;
+use bril_utils::cfg::*;
+use bril_utils::form_blocks::*;
+use bril_utils::dominators::*;
+use bril_utils::control_dep::control_dependence;
+use bril_utils::dot::*;
+use bril_utils::loops::{find_loops, LoopForest};
 use bril_rs::load_abstract_program;
 use clap::Parser;
+use indexmap::IndexMap;
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -18,31 +21,123 @@ struct Args {
 
     #[clap(short, long)]
     validate : bool,
+
+    /// Print the back edges, the loop nest and any irreducible regions.
+    #[clap(short, long)]
+    loops : bool,
+
+    /// Print post-dominators instead of dominators, or the post-dominator
+    /// tree with `-t`.
+    #[clap(long)]
+    post : bool,
+
+    /// Print the blocks each block is control dependent on.
+    #[clap(long)]
+    cdg : bool,
+
+    /// Print the results as JSON, keyed by function.
+    #[clap(short, long)]
+    json : bool,
+
+    /// Print each function's CFG as a Graphviz graph with its loop nest and
+    /// the dominator tree, or the dominance frontier with `-f`.
+    #[clap(long)]
+    dot : bool,
+}
+
+fn sorted_names<'a>(cfg : &Cfg, blocks : impl IntoIterator<Item=&'a i32>) -> Vec<String> {
+    let mut names : Vec<String> = blocks.into_iter().map(|b| cfg.name(*b).clone()).collect();
+    names.sort();
+    names
+}
+
+fn edge_names(cfg : &Cfg, edges : &[(i32, i32)]) -> Vec<String> {
+    let mut names : Vec<String> = edges.iter().map(|(a, b)| format!("{} -> {}", cfg.name(*a), cfg.name(*b))).collect();
+    names.sort();
+    names
+}
+
+fn format_loops(cfg : &Cfg, forest : &LoopForest) -> (String, serde_json::Value) {
+    let mut text = String::from("Loops\n--------------------------------\n");
+    text += &format!("back edges: {:?}\n", edge_names(cfg, &forest.back_edges));
+    let mut loops = vec![];
+    for l in &forest.loops {
+        let parent = l.parent.map(|p| cfg.name(forest.get_loop(p).header).clone());
+        let (latches, body, exits) = (sorted_names(cfg, &l.latches), sorted_names(cfg, &l.body), edge_names(cfg, &l.exits));
+        text += &format!("{}loop {}: depth {}, latches {latches:?}, body {body:?}, exits {exits:?}\n",
+            "  ".repeat(l.depth - 1), cfg.name(l.header), l.depth);
+        loops.push(serde_json::json!({
+            "header" : cfg.name(l.header), "depth" : l.depth, "parent" : parent,
+            "latches" : latches, "body" : body, "exits" : exits,
+        }));
+    }
+    let mut irreducible = vec![];
+    for r in &forest.irreducible {
+        let (entries, blocks) = (sorted_names(cfg, &r.entries), sorted_names(cfg, &r.blocks));
+        text += &format!("irreducible: entries {entries:?}, blocks {blocks:?}\n");
+        irreducible.push(serde_json::json!({"entries" : entries, "blocks" : blocks}));
+    }
+    let value = serde_json::json!({
+        "back_edges" : edge_names(cfg, &forest.back_edges), "loops" : loops, "irreducible" : irreducible,
+    });
+    (text, value)
 }
 
 fn main() {
     let args = Args::parse();
     let mut program = load_abstract_program();
+    let mut results = IndexMap::new();
     for func in &mut program.functions {
         let blocks = form_blocks(&func);
         let cfg = form_cfg(blocks);
-        let mut pred_sorted : Vec<_> = cfg.pred.iter().collect();
-        pred_sorted.sort_by_key(|a| a.0);
-        let mut succ_sorted : Vec<_> = cfg.succ.iter().collect();
-        succ_sorted.sort_by_key(|a| a.0);
-        if args.tree {
-           let dom_tree = form_dom_tree(&cfg);
-            print_dominator_tree(&dom_tree);
+        if args.dot {
+            let loops = find_loops(&cfg);
+            let dom_tree = form_dom_tree(&cfg);
+            let frontier = get_dominance_frontier_num(&cfg);
+            let overlays = if args.frontier {
+                Overlays {frontier : Some(&frontier), loops : Some(&loops), ..Overlays::default()}
+            } else {
+                Overlays {dom_tree : Some(&dom_tree), loops : Some(&loops), ..Overlays::default()}
+            };
+            print!("{}", cfg_to_dot(&func.name, &cfg, &overlays));
+            continue;
+        }
+        let (text, value) = if args.post && args.tree {
+            let post_dom_tree = form_post_dom_tree(&cfg);
+            (post_dom_tree.to_string(), serde_json::to_value(&post_dom_tree))
+        } else if args.post {
+            let pdoms = BlockSets::new("Post-dominators", &find_post_dominators(&cfg));
+            (pdoms.to_string(), serde_json::to_value(&pdoms))
+        } else if args.cdg {
+            let cdg = control_dependence(&cfg);
+            let deps = cdg.deps.iter().map(|(b, ds)|
+                (cfg.name(*b).clone(), ds.iter().map(|d| cfg.name(*d).clone()).collect())).collect();
+            let deps = BlockSets::new("Control dependences", &deps);
+            (deps.to_string(), serde_json::to_value(&deps))
+        } else if args.tree {
+            let dom_tree = form_dom_tree(&cfg);
+            (dom_tree.to_string(), serde_json::to_value(&dom_tree))
         } else if args.frontier {
-            let frontier = get_dominance_frontier(&cfg);
-            print_dominance_frontier(&frontier);
+            let frontier = BlockSets::new("Frontier", &get_dominance_frontier(&cfg));
+            (frontier.to_string(), serde_json::to_value(&frontier))
+        } else if args.loops {
+            let (text, value) = format_loops(&cfg, &find_loops(&cfg));
+            (text, Ok(value))
         } else if args.validate {
             let doms = find_dominators_num(&cfg);
             validate_dominators(&cfg, doms);
-            println!("Dominators are correct!");
+            ("Dominators are correct!\n".to_string(), serde_json::to_value(true))
         } else {
-            let doms = find_dominators(&cfg);
-            print_dominators(&doms);
+            let doms = BlockSets::new("Dominators", &find_dominators(&cfg));
+            (doms.to_string(), serde_json::to_value(&doms))
+        };
+        if args.json {
+            results.insert(func.name.clone(), value.unwrap());
+        } else {
+            print!("{text}");
         }
     }
+    if args.json && !args.dot {
+        println!("{}", serde_json::to_string_pretty(&results).unwrap());
+    }
 }
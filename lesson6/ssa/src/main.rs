@@ -1,5 +1,9 @@
 use bril_rs::{load_abstract_program, output_abstract_program};
-use ssa::ssa::{to_ssa, from_ssa};
+use bril_utils::ssa::{to_ssa_mode, from_ssa, SsaMode};
+use bril_utils::sccp::sccp;
+use bril_utils::ssa::verify::verify_ssa;
+use bril_utils::tdce::trivial_dce;
+use bril_utils::verify;
 use clap::Parser;
 
 #[derive(Parser, Debug)]
@@ -12,20 +16,57 @@ struct Args {
 
     #[clap(short, long)]
     roundtrip : bool,
+
+    #[clap(short, long)]
+    sccp : bool,
+
+    /// Where to place phis when converting to SSA.
+    #[clap(short, long, default_value = "pruned")]
+    mode : SsaMode,
+
+    /// Check that the function is in SSA form: the input of `--from-ssa`,
+    /// otherwise the output of `to_ssa`. Violations go to stderr.
+    #[clap(long)]
+    verify : bool,
+
+    /// Check for malformed Bril before and after the pass. Diagnostics go
+    /// to stderr and the exit code is 1 if the output has any.
+    #[clap(long)]
+    lint : bool,
 }
 
 fn main() {
     let args = Args::parse();
     let mut program = load_abstract_program();
+    if args.lint {
+        verify::report("input", &program);
+    }
+    let mut violations = vec![];
     for func in &mut program.functions {
         if args.from_ssa {
+            if args.verify {
+                violations.extend(verify_ssa(func));
+            }
             from_ssa(func);
-        } else if args.roundtrip {
-            to_ssa(func);
+            continue;
+        }
+        to_ssa_mode(func, args.mode);
+        if args.verify {
+            violations.extend(verify_ssa(func));
+        }
+        if args.roundtrip {
             from_ssa(func);
-        } else {
-            to_ssa(func);
+        } else if args.sccp {
+            sccp(func);
+            trivial_dce(func);
         }
     }
     output_abstract_program(&program);
+    let malformed = args.lint && verify::report("output", &program);
+    for v in &violations {
+        eprintln!("{v}");
+    }
+    if malformed || !violations.is_empty() {
+        std::process::exit(1);
+    }
 }
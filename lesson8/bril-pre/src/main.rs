@@ -0,0 +1,34 @@
+use bril_rs::{load_abstract_program, output_abstract_program};
+use bril_utils::pre::pre;
+use bril_utils::tdce::trivial_dce;
+use bril_utils::verify;
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(short, long)]
+    no_dce : bool,
+
+    /// Check for malformed Bril before and after the pass. Diagnostics go
+    /// to stderr and the exit code is 1 if the output has any.
+    #[clap(long)]
+    lint : bool,
+}
+
+fn main() {
+    let args = Args::parse();
+    let mut program = load_abstract_program();
+    if args.lint {
+        verify::report("input", &program);
+    }
+    for func in &mut program.functions {
+        pre(func);
+        if !args.no_dce {
+            trivial_dce(func);
+        }
+    }
+    output_abstract_program(&program);
+    if args.lint && verify::report("output", &program) {
+        std::process::exit(1);
+    }
+}